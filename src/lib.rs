@@ -0,0 +1,45 @@
+//! # lib.rs – read SQLite database files from Rust
+//!
+//! ```text
+//!  Database::load(path) ──▶ db.query("SELECT …") ──▶ Rows ──▶ Row ──▶ RecordValue
+//!                      ├──▶ db.table_cursor(root) ──▶ Record (one per table row)
+//!                      └──▶ db.schema() ──▶ Table / Index / View / Trigger
+//! ```
+//!
+//! A small, read-only reader for the SQLite 3 file format with a SQL front
+//! end: SELECT with WHERE, JOIN, GROUP BY/HAVING, ORDER BY and LIMIT, using
+//! rowid seeks and indexes where it can. Databases in WAL mode are read as
+//! of the last commit in their `-wal` file when they are opened.
+//!
+//! ```no_run
+//! use codecrafters_sqlite::{Database, RecordValue};
+//!
+//! # fn main() -> codecrafters_sqlite::Result<()> {
+//! let db = Database::load("sample.db")?;
+//! let rows = db.query("SELECT name, color FROM apples ORDER BY name")?;
+//! println!("{}", rows.columns().join("|"));
+//! for row in rows {
+//!     if let Some(RecordValue::Text(name)) = row?.get(0) {
+//!         println!("{}", name);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Stability
+//!
+//! Everything exported here follows semver. `Error` is `#[non_exhaustive]`,
+//! so new failure kinds can be added in minor releases. `Row` and `Rows`
+//! only expose methods, which leaves room to change how results are
+//! produced. The SQL parser, the expression evaluator and the page decoder
+//! are internal and may change at any time.
+//!
+mod sqlite;
+
+pub use sqlite::sql::ParseError;
+pub use sqlite::{
+    Affinity, CacheStats, Column, Database, DatabaseHeader, Error, Index, IndexColumn, QueryError,
+    Record, RecordValue, Result, Row, RowidRange, Rows, Schema, Table, TableCursor, TextEncoding,
+    Trigger, View,
+};
@@ -0,0 +1,392 @@
+//! # listing.rs – `.tables`, `.indexes`, `.schema` and `.fullschema`
+//!
+//! ```text
+//!  .tables [LIKE]        tables and views             ┐ names, sorted, as many
+//!  .indexes [LIKE]       indexes of matching tables   ┘ columns as fit in 80
+//!  .schema [PATTERN]     CREATE statements of matching tables and everything
+//!                        on them, in the order they were created
+//!  .fullschema           every CREATE but SQLite's own, then sqlite_stat*
+//!                        rows as INSERTs
+//! ```
+//!
+//! Everything is printed the way the sqlite3 shell prints it, so output can
+//! be diffed against it. Patterns follow the shell too: `.tables` and
+//! `.indexes` take a LIKE pattern (`%` and `_`, any case), while `.schema`
+//! switches to GLOB (`*`, `?`, `[...]`) when its pattern has one of those.
+//!
+use anyhow::bail;
+
+use codecrafters_sqlite::{Database, RecordValue};
+
+/// The names sqlite3 accepts for the schema table itself.
+const SCHEMA_TABLE_NAMES: [&str; 4] = [
+    "sqlite_master",
+    "sqlite_schema",
+    "sqlite_temp_master",
+    "sqlite_temp_schema",
+];
+
+/// One row of sqlite_schema.
+struct Entry {
+    kind: String,
+    name: String,
+    table: String,
+    sql: Option<String>,
+}
+
+// Every row of sqlite_schema, in rowid order: the order the objects were
+// created in.
+fn entries(db: &Database) -> anyhow::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for row in db.query("SELECT type, name, tbl_name, sql FROM sqlite_schema")? {
+        let row = row?;
+        let text = |column| match row.get(column) {
+            Some(RecordValue::Text(text)) => Some(text.clone()),
+            _ => None,
+        };
+        entries.push(Entry {
+            kind: text(0).unwrap_or_default(),
+            name: text(1).unwrap_or_default(),
+            table: text(2).unwrap_or_default(),
+            sql: text(3),
+        });
+    }
+    Ok(entries)
+}
+
+/// `.tables ?PATTERN?`: tables and views, leaving out SQLite's own.
+pub fn tables(db: &Database, args: &[String]) -> anyhow::Result<()> {
+    // Like sqlite3, extra arguments are ignored here but not by `.indexes`.
+    let pattern = args.first().map_or("%", String::as_str);
+    let names = entries(db)?
+        .into_iter()
+        .filter(|entry| matches!(entry.kind.as_str(), "table" | "view"))
+        .filter(|entry| !like("sqlite_%", &entry.name) && like(pattern, &entry.name))
+        .map(|entry| entry.name)
+        .collect();
+    print_in_columns(names);
+    Ok(())
+}
+
+/// `.indexes ?PATTERN?`: indexes on the tables whose names match.
+pub fn indexes(db: &Database, args: &[String]) -> anyhow::Result<()> {
+    let pattern = match args {
+        [] => "%",
+        [pattern] => pattern.as_str(),
+        _ => bail!("Usage: .indexes ?LIKE-PATTERN?"),
+    };
+    let names = entries(db)?
+        .into_iter()
+        .filter(|entry| entry.kind == "index" && like(pattern, &entry.table))
+        .map(|entry| entry.name)
+        .collect();
+    print_in_columns(names);
+    Ok(())
+}
+
+/// `.schema ?--nosys? ?PATTERN?`: the CREATE statements of every object
+/// whose table matches `PATTERN`.
+pub fn schema(db: &Database, args: &[String]) -> anyhow::Result<()> {
+    let mut pattern = None;
+    let mut no_system_tables = false;
+    for arg in args {
+        match arg.as_str() {
+            "-nosys" | "--nosys" => no_system_tables = true,
+            option if option.starts_with('-') => bail!("Unknown option: \"{}\"", option),
+            _ if pattern.is_none() => pattern = Some(arg.as_str()),
+            _ => bail!("Usage: .schema ?--nosys? ?LIKE-PATTERN?"),
+        }
+    }
+
+    if let Some(pattern) = pattern {
+        if SCHEMA_TABLE_NAMES.iter().any(|name| like(pattern, name)) {
+            print_schema_line(&format!(
+                "CREATE TABLE {} (\n  type text,\n  name text,\n  tbl_name text,\n  \
+                 rootpage integer,\n  sql text\n)",
+                pattern
+            ));
+        }
+    }
+    let matches_pattern = |table: &str| match pattern {
+        None => true,
+        Some(pattern) => {
+            // A qualified pattern like `main.t*` names the schema too.
+            let table = if pattern.contains('.') {
+                format!("main.{}", table)
+            } else {
+                table.to_string()
+            };
+            if pattern.contains(['*', '?', '[']) {
+                glob(pattern, &table.to_ascii_lowercase())
+            } else {
+                like_escaped(pattern, &table, Some('\\'))
+            }
+        }
+    };
+    for entry in entries(db)? {
+        let Some(sql) = &entry.sql else { continue };
+        if no_system_tables && entry.name.starts_with("sqlite_") {
+            continue;
+        }
+        if matches_pattern(&entry.table) {
+            print_schema_line(sql);
+        }
+    }
+    Ok(())
+}
+
+/// `.fullschema`: every CREATE statement but SQLite's own, then what
+/// ANALYZE gathered, as statements that would restore it.
+pub fn full_schema(db: &Database, args: &[String]) -> anyhow::Result<()> {
+    if !args.is_empty() {
+        bail!("Usage: .fullschema");
+    }
+    let entries = entries(db)?;
+    for entry in &entries {
+        if let Some(sql) = &entry.sql {
+            if !like("sqlite_%", &entry.name) {
+                print_schema_line(sql);
+            }
+        }
+    }
+
+    let has_table = |name: &str| {
+        entries
+            .iter()
+            .any(|entry| entry.kind == "table" && entry.name == name)
+    };
+    if !entries
+        .iter()
+        .any(|entry| glob("sqlite_stat[134]", &entry.name))
+    {
+        println!("/* No STAT tables available */");
+        return Ok(());
+    }
+    println!("ANALYZE sqlite_schema;");
+    for table in ["sqlite_stat1", "sqlite_stat4"] {
+        if !has_table(table) {
+            continue;
+        }
+        for row in db.query(&format!("SELECT * FROM {}", table))? {
+            let values: Vec<String> = row?.values().iter().map(sql_literal).collect();
+            println!("INSERT INTO {} VALUES({});", table, values.join(","));
+        }
+    }
+    println!("ANALYZE sqlite_schema;");
+    Ok(())
+}
+
+// One stored CREATE statement and its `;`. sqlite3 prints `CREATE TABLE
+// "name"` as `CREATE TABLE IF NOT EXISTS "name"`, and so do we.
+fn print_schema_line(sql: &str) {
+    match sql.strip_prefix("CREATE TABLE ") {
+        Some(rest) if rest.starts_with(['\'', '"']) => {
+            println!("CREATE TABLE IF NOT EXISTS {};", rest)
+        }
+        _ => println!("{};", sql),
+    }
+}
+
+// Names sorted and laid out top to bottom, then left to right, in as many
+// columns as fit in 80 characters. Widths count bytes, as in sqlite3.
+fn print_in_columns(mut names: Vec<String>) {
+    if names.is_empty() {
+        return;
+    }
+    names.sort();
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    let columns = (80 / (width + 2)).max(1);
+    let rows = names.len().div_ceil(columns);
+    for row in 0..rows {
+        let line: Vec<String> = names[row..]
+            .iter()
+            .step_by(rows)
+            .map(|name| format!("{}{}", name, " ".repeat(width - name.len())))
+            .collect();
+        println!("{}", line.join("  "));
+    }
+}
+
+// A value as SQL source: quoted text, X'..' blobs, bare numbers.
+fn sql_literal(value: &RecordValue) -> String {
+    match value {
+        RecordValue::Null => "NULL".to_string(),
+        RecordValue::Text(text) => format!("'{}'", text.replace('\'', "''")),
+        RecordValue::Blob(bytes) => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("X'{}'", hex)
+        }
+        number => number.to_string(),
+    }
+}
+
+fn like(pattern: &str, text: &str) -> bool {
+    like_escaped(pattern, text, None)
+}
+
+// SQL LIKE: `%` is any run of characters, `_` any one, ASCII letters match
+// either case, and `escape` makes the character after it literal.
+fn like_escaped(pattern: &str, text: &str, escape: Option<char>) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    wildcard_match(&pattern, &text, true, |pattern| match pattern {
+        ['%', rest @ ..] => (Token::Star, rest),
+        ['_', rest @ ..] => (Token::Any, rest),
+        [c, escaped, rest @ ..] if Some(*c) == escape => (Token::Char(*escaped), rest),
+        [c, rest @ ..] => (Token::Char(*c), rest),
+        [] => unreachable!("no token in an empty pattern"),
+    })
+}
+
+// GLOB: `*` is any run of characters, `?` any one, `[abc]`, `[a-z]` and
+// `[^abc]` a set; everything else matches itself, case included.
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    wildcard_match(&pattern, &text, false, |pattern| match pattern {
+        ['*', rest @ ..] => (Token::Star, rest),
+        ['?', rest @ ..] => (Token::Any, rest),
+        ['[', rest @ ..] => {
+            let (negated, set) = match rest {
+                ['^', set @ ..] => (true, set),
+                _ => (false, rest),
+            };
+            // A `]` straight after `[` or `[^` is part of the set.
+            match set.iter().skip(1).position(|&c| c == ']') {
+                Some(close) => (
+                    Token::Set {
+                        negated,
+                        set: &set[..close + 1],
+                    },
+                    &set[close + 2..],
+                ),
+                None => (Token::Unclosed, &[]),
+            }
+        }
+        [c, rest @ ..] => (Token::Char(*c), rest),
+        [] => unreachable!("no token in an empty pattern"),
+    })
+}
+
+// One piece of a LIKE or GLOB pattern.
+enum Token<'a> {
+    // `%` or `*`: any run of characters, none included.
+    Star,
+    // `_` or `?`: any one character.
+    Any,
+    Char(char),
+    // `[...]`, or `[^...]` when negated.
+    Set { negated: bool, set: &'a [char] },
+    // A `[` with no `]` to close it, which matches nothing.
+    Unclosed,
+}
+
+impl Token<'_> {
+    fn matches(&self, t: char, ignore_case: bool) -> bool {
+        match self {
+            Token::Star | Token::Any => true,
+            Token::Char(c) if ignore_case => c.eq_ignore_ascii_case(&t),
+            Token::Char(c) => *c == t,
+            Token::Set { negated, set } => {
+                let mut found = false;
+                let mut i = 0;
+                while i < set.len() {
+                    if set.get(i + 1) == Some(&'-') && i + 2 < set.len() {
+                        found |= (set[i]..=set[i + 2]).contains(&t);
+                        i += 3;
+                    } else {
+                        found |= set[i] == t;
+                        i += 1;
+                    }
+                }
+                found != *negated
+            }
+            Token::Unclosed => false,
+        }
+    }
+}
+
+// Match `text` against a pattern that `next_token` splits into tokens.
+// Every token but a star takes exactly one character, so on a mismatch it
+// is enough to go back to the last star and let it take one more: earlier
+// stars can't do any better. That keeps the work at most pattern length
+// times text length, however many stars there are.
+fn wildcard_match<'p>(
+    pattern: &'p [char],
+    text: &[char],
+    ignore_case: bool,
+    next_token: impl Fn(&'p [char]) -> (Token<'p>, &'p [char]),
+) -> bool {
+    let (mut p, mut t) = (pattern, 0);
+    // The pattern after the last star, and where in the text it was last
+    // tried from.
+    let mut last_star: Option<(&[char], usize)> = None;
+    loop {
+        if !p.is_empty() {
+            match next_token(p) {
+                (Token::Star, rest) => {
+                    last_star = Some((rest, t));
+                    p = rest;
+                    continue;
+                }
+                (token, rest) if t < text.len() && token.matches(text[t], ignore_case) => {
+                    p = rest;
+                    t += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        } else if t == text.len() {
+            return true;
+        }
+        match last_star {
+            Some((after, from)) if from < text.len() => {
+                last_star = Some((after, from + 1));
+                p = after;
+                t = from + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn like_ignores_ascii_case_and_honours_escapes() {
+        assert!(like("app%", "Apples"));
+        assert!(like("_pples", "apples"));
+        assert!(!like("apple", "apples"));
+        assert!(like("sqlite_%", "sqlite_sequence"));
+        assert!(like("sqlite_%", "sqliteXseq"));
+        assert!(!like_escaped("sqlite\\_%", "sqliteXseq", Some('\\')));
+        assert!(like_escaped("sqlite\\_%", "sqlite_seq", Some('\\')));
+        assert!(like_escaped("%\\%%", "100%", Some('\\')));
+        assert!(!like_escaped("%\\%", "100", Some('\\')));
+    }
+
+    #[test]
+    fn glob_matches_sets_and_keeps_case() {
+        assert!(glob("sqlite_stat[134]", "sqlite_stat4"));
+        assert!(!glob("sqlite_stat[134]", "sqlite_stat2"));
+        assert!(glob("[a-c]*", "banana"));
+        assert!(glob("[^a-c]?", "xy"));
+        assert!(glob("[]x]", "]"));
+        assert!(!glob("App*", "apples"));
+        assert!(glob("a?p*s", "apples"));
+        assert!(!glob("[abc", "a"));
+        assert!(glob("*[x]", "abx"));
+    }
+
+    #[test]
+    fn many_stars_do_not_backtrack_exponentially() {
+        let text = "a".repeat(10_000);
+        assert!(!like("%a%a%a%a%a%a%a%a%b", &text));
+        assert!(like("%a%a%a%a%a%a%a%a%", &text));
+        assert!(!glob("*a*a*a*a*a*a*a*a*b", &text));
+        assert!(glob(&"*a".repeat(20), &text));
+        assert!(like("%a%B", &format!("{}b", text)));
+    }
+}
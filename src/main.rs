@@ -1,330 +1,253 @@
 //! # main.rs – tiny CLI that speaks (very small) SQL
 //!
 //! ```text
-//! 1) Read command-line args
+//! 1) Read command-line args: [--mode MODE] [--headers] [--separator SEP]
 //!        │
 //!        ▼
 //! 2) Database::load()
 //!        │
 //!        ▼
-//! 3) Decide command ─┬─ .dbinfo
-//!                    ├─ .tables
-//!                    ├─ select count(*)
-//!                    └─ SELECT columns FROM table [WHERE ...]
+//! 3) One command given? run it. None? start the shell (see `shell.rs`),
+//!    which runs each command typed at its prompt.
+//!        │
+//!        ▼
+//! 4) Decide command ─┬─ .dbinfo
+//!                    ├─ .tables, .indexes, .schema, .fullschema (listing.rs)
+//!                    ├─ .mode, .headers, .separator (output.rs)
+//!                    ├─ .help, .quit, .exit
+//!                    └─ anything else → db.query() → print rows in the mode
+//!                         ├─ select count(*) from table
+//!                         └─ SELECT exprs FROM table {[LEFT] JOIN table ON expr}
+//!                              [WHERE expr]
+//!                              [GROUP BY ...] [HAVING expr]
+//!                              [ORDER BY ...] [LIMIT n [OFFSET m]]
 //! ```
 //!
-//! All heavy lifting (page parsing, searching) lives in `sqlite::db`.
+//! This is a thin client: opening the file, planning and running queries
+//! all live in the `codecrafters_sqlite` library (see `lib.rs`).
 //!
-mod sqlite;
+mod listing;
+mod output;
+mod shell;
 
-use std::env;
+use std::{env, io, ops::ControlFlow};
 
 use anyhow::bail;
 
-use sqlite::{Database, Record, RecordValue};
+use codecrafters_sqlite::{Database, RecordValue};
+
+use output::{Mode, Output};
+
+const HELP: &str = "\
+.dbinfo                  Show status information about the database
+.exit                    Exit the shell
+.fullschema              Show schema and the content of sqlite_stat tables
+.headers on|off          Turn display of headers on or off
+.help                    Show this message
+.indexes ?TABLE?         Show names of indexes
+.mode ?MODE?             Set output mode: box column csv json line list
+                         markdown table
+.quit                    Exit the shell
+.schema ?--nosys? ?PAT?  Show the CREATE statements matching PAT
+.separator COL ?ROW?     Change the column and row separators
+.tables ?TABLE?          List names of tables matching LIKE pattern TABLE";
+
+const USAGE: &str = "Usage: codecrafters-sqlite [--mode MODE] [--headers] [--separator SEP] \
+                     <database path> [COMMAND]";
+
+// .dbinfo: the header fields and schema counts, laid out like the sqlite3
+// shell prints them.
+fn print_dbinfo(db: &Database) -> anyhow::Result<()> {
+    let header = db.header();
+    let encoding = header.text_encoding;
+    let fields: [(&str, String); 16] = [
+        ("database page size:", header.page_size.to_string()),
+        ("write format:", header.write_version.to_string()),
+        ("read format:", header.read_version.to_string()),
+        ("reserved bytes:", header.reserved_bytes.to_string()),
+        (
+            "file change counter:",
+            header.file_change_counter.to_string(),
+        ),
+        ("database page count:", header.page_count.to_string()),
+        (
+            "freelist page count:",
+            header.freelist_page_count.to_string(),
+        ),
+        ("schema cookie:", header.schema_cookie.to_string()),
+        ("schema format:", header.schema_format.to_string()),
+        ("default cache size:", header.default_cache_size.to_string()),
+        (
+            "autovacuum top root:",
+            header.autovacuum_top_root.to_string(),
+        ),
+        ("incremental vacuum:", header.incremental_vacuum.to_string()),
+        (
+            "text encoding:",
+            format!("{} ({})", encoding.code(), encoding.name()),
+        ),
+        ("user version:", header.user_version.to_string()),
+        ("application id:", header.application_id.to_string()),
+        ("software version:", header.sqlite_version.to_string()),
+    ];
+    for (label, value) in fields {
+        println!("{:<20} {}", label, value);
+    }
 
-// Add this function before your main() function
-// Friendly formatter: turn any RecordValue into a printable string.
-fn format_record_value(value: &RecordValue) -> String {
-    match value {
-        RecordValue::Text(text) => text.clone(),
-        RecordValue::Int(number) => number.to_string(),
-        RecordValue::Real(float) => float.to_string(),
-        RecordValue::Null => "NULL".to_string(),
-        RecordValue::Blob(_) => "[BLOB]".to_string(),
+    // Schema size is the total length (in characters) of every CREATE
+    // statement.
+    let (mut tables, mut indexes, mut triggers, mut views) = (0, 0, 0, 0);
+    let mut schema_size = 0;
+    for row in db.query("SELECT type, sql FROM sqlite_schema")? {
+        let row = row?;
+        match row.get(0) {
+            Some(RecordValue::Text(typ)) if typ == "table" => tables += 1,
+            Some(RecordValue::Text(typ)) if typ == "index" => indexes += 1,
+            Some(RecordValue::Text(typ)) if typ == "trigger" => triggers += 1,
+            Some(RecordValue::Text(typ)) if typ == "view" => views += 1,
+            _ => {}
+        }
+        if let Some(RecordValue::Text(sql)) = row.get(1) {
+            schema_size += sql.chars().count();
+        }
     }
+    println!("{:<20} {}", "number of tables:", tables);
+    println!("{:<20} {}", "number of indexes:", indexes);
+    println!("{:<20} {}", "number of triggers:", triggers);
+    println!("{:<20} {}", "number of views:", views);
+    println!("{:<20} {}", "schema size:", schema_size);
+    Ok(())
 }
 
-// Simple helper: does the given record match the WHERE condition?
-fn matches_where_condition(record: &Record, where_column_pos: usize, expected_value: &str) -> bool {
-    let actual_value = &record.values[where_column_pos];
-    let actual_string = format_record_value(actual_value);
-    actual_string == expected_value
+// Split a dot-command into words like sqlite3 does: at whitespace, except
+// inside '...' or "...". Backslash escapes work in "..." only.
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            return args;
+        };
+        let mut arg = String::new();
+        if first == '\'' {
+            arg.extend(chars.by_ref().take_while(|&c| c != first));
+        } else if first == '"' {
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => match chars.next() {
+                        Some('t') => arg.push('\t'),
+                        Some('n') => arg.push('\n'),
+                        Some('r') => arg.push('\r'),
+                        Some(other) => arg.push(other),
+                        None => arg.push('\\'),
+                    },
+                    c => arg.push(c),
+                }
+            }
+        } else {
+            arg.push(first);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+}
+
+// `.headers` and friends take any of the spellings sqlite3 does.
+fn parse_switch(arg: &str) -> anyhow::Result<bool> {
+    match arg.to_ascii_lowercase().as_str() {
+        "on" | "yes" | "true" | "1" => Ok(true),
+        "off" | "no" | "false" | "0" => Ok(false),
+        _ => bail!("not a boolean value: \"{}\"", arg),
+    }
+}
+
+// Run one dot-command or SQL statement, printing what it produces in the
+// current output mode. Breaks when the command asks to leave the shell.
+fn run_command(
+    db: &Database,
+    output: &mut Output,
+    command: &str,
+) -> anyhow::Result<ControlFlow<()>> {
+    let Some(dot_command) = command.strip_prefix('.') else {
+        output.print(&mut io::stdout().lock(), db.query(command)?)?;
+        return Ok(ControlFlow::Continue(()));
+    };
+
+    let words = split_args(dot_command);
+    let (name, args) = words
+        .split_first()
+        .map_or(("", &[][..]), |(name, args)| (name.as_str(), args));
+    match name {
+        "dbinfo" => print_dbinfo(db)?,
+        "tables" => listing::tables(db, args)?,
+        "indexes" | "indices" => listing::indexes(db, args)?,
+        "schema" => listing::schema(db, args)?,
+        "fullschema" => listing::full_schema(db, args)?,
+        "mode" => match args {
+            [] => println!("current output mode: {}", output.mode().name()),
+            [mode] => output.set_mode(Mode::from_name(mode)?),
+            _ => bail!("Usage: .mode ?MODE?"),
+        },
+        "headers" => match args {
+            [switch] => output.set_headers(parse_switch(switch)?),
+            _ => bail!("Usage: .headers on|off"),
+        },
+        "separator" => match args {
+            [column] => output.set_separators(column, None),
+            [column, row] => output.set_separators(column, Some(row)),
+            _ => bail!("Usage: .separator COL ?ROW?"),
+        },
+        "help" => println!("{}", HELP),
+        "quit" | "exit" => return Ok(ControlFlow::Break(())),
+        _ => bail!(
+            "unknown command or invalid arguments: \"{}\". Enter \".help\" for help",
+            name
+        ),
+    }
+    Ok(ControlFlow::Continue(()))
 }
 
 // --------------------------------------------------------------------
 // main() – frontend dispatcher: open DB and route the command.
 // --------------------------------------------------------------------
 fn main() -> anyhow::Result<()> {
-    let args = env::args().collect::<Vec<_>>();
-    match args.len() {
-        0 | 1 => bail!("Missing <database path> and <command>"),
-        2 => bail!("Missing <command>"),
-        _ => {}
-    }
-    let (db_path, command) = (&args[1], &args[2]);
-
-    let db = Database::load(db_path)?;
-
-    match command.as_str() {
-        ".dbinfo" => {
-            println!("database page size: {}", db.page_size);
-            println!("number of tables: {}", db.root_page.cell_pointers.len());
+    let mut output = Output::default();
+    let mut positional = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        // Options come before the database, like in sqlite3; after it,
+        // everything is the command.
+        if !positional.is_empty() || !arg.starts_with('-') {
+            positional.push(arg);
+            continue;
         }
-        ".tables" => {
-            let table_names: Vec<_> = db
-                .root_page
-                .records()
-                .map(|r| match &r.values[2] {
-                    RecordValue::Text(s) => s.clone(),
-                    _ => panic!("Expected table name, got {:?}", r.values[0]),
-                })
-                .collect();
-            let table_names = table_names.join(" ");
-
-            println!("{}", table_names);
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("{} needs a value", arg))
+        };
+        match arg.trim_start_matches('-') {
+            "mode" => output.set_mode(Mode::from_name(&value()?)?),
+            "headers" | "header" => output.set_headers(true),
+            "noheaders" | "noheader" => output.set_headers(false),
+            "separator" => output.set_separators(&value()?, None),
+            _ => bail!("unknown option: {}\n{}", arg, USAGE),
         }
-        command if command.starts_with("select count(*) from") => {
-            let parts: Vec<&str> = command.split_whitespace().collect();
-            let table_name = parts.last().unwrap();
-
-            let table_record = db
-                .root_page
-                .records()
-                .find(|record| match &record.values[1] {
-                    RecordValue::Text(name) => name == table_name,
-                    _ => false,
-                });
-            let table_record = match table_record {
-                Some(record) => record,
-                None => bail!("Table not found: {}", table_name),
-            };
-            let root_page = match &table_record.values[3] {
-                RecordValue::Int(page_num) => *page_num as usize,
-                _ => bail!("Invalid root page value for table: {}", table_name),
-            };
-            let table_page = db.load_page(db_path, root_page)?;
-
-            let count = table_page.records().count();
-            println!("{}", count);
-        }
-        command
-            if command.to_lowercase().starts_with("select")
-                && command.to_lowercase().contains("from") =>
-        {
-            let parts: Vec<&str> = command.split_whitespace().collect();
-
-            let select_pos = parts
-                .iter()
-                .position(|&word| word.to_lowercase() == "select")
-                .unwrap();
-            let from_pos = parts
-                .iter()
-                .position(|&word| word.to_lowercase() == "from")
-                .unwrap();
-            let where_pos = parts
-                .iter()
-                .position(|&word| word.to_lowercase() == "where");
-
-            // let column_name = parts[select_pos + 1];
-
-            let columns_slice = &parts[select_pos + 1..from_pos];
-            let columns_string = columns_slice.join(" ");
-            let requested_column_names: Vec<&str> =
-                columns_string.split(",").map(|s| s.trim()).collect();
-
-            // eprintln!("requested_column_names: {:?}", requested_column_names);
-
-            let table_name = parts[from_pos + 1];
-
-            let where_clause = if let Some(where_position) = where_pos {
-                let where_parts = &parts[where_position + 1..];
-                Some(where_parts.join(" "))
-            } else {
-                None
-            };
-
-            // eprintln!("column_name: {}", column_name);
-            // eprintln!("table_name: {}", table_name);
-
-            let table_record = db
-                .root_page
-                .records()
-                .find(|record| match &record.values[1] {
-                    RecordValue::Text(name) => name == table_name,
-                    _ => false,
-                });
-
-            let table_record = match table_record {
-                Some(record) => record,
-                None => bail!("Table '{}' not found", table_name),
-            };
-            // Extract CREATE TABLE statement
-            let create_table_sql = match &table_record.values[4] {
-                RecordValue::Text(sql) => sql,
-                _ => bail!("Invalid CREATE TABLE statement for table '{}'", table_name),
-            };
-
-            // Extract rootpage
-            let rootpage = match &table_record.values[3] {
-                RecordValue::Int(page_num) => *page_num as usize,
-                _ => bail!("Invalid rootpage value for table '{}'", table_name),
-            };
-
-            // eprintln!("CREATE TABLE SQL: {}", create_table_sql);
-
-            let start = create_table_sql.find('(').unwrap() + 1;
-            // let end = create_table_sql.rfind(')').unwrap();
-            let columns_part = &create_table_sql[start..];
-
-            // eprintln!("columns_part: {}", columns_part);
-
-            let column_definitions: Vec<&str> = columns_part.split(',').collect();
-
-            // eprintln!("column_definitions: {:?}", column_definitions);
-
-            let schema_column_names: Vec<&str> = column_definitions
-                .iter()
-                .map(|def| def.trim().split_whitespace().next().unwrap())
-                .collect();
-
-            let (where_column, where_value, where_column_position) =
-                if let Some(where_string) = &where_clause {
-                    // Parse WHERE clause: handle quoted strings with spaces
-                    let equal_pos = where_string.find(" = ");
-                    if equal_pos.is_none() {
-                        bail!("Invalid WHERE clause format. Expected: column = value");
-                    }
-                    let equal_pos = equal_pos.unwrap();
-
-                    let where_column = where_string[..equal_pos].trim();
-                    let where_value_raw = where_string[equal_pos + 3..].trim(); // Skip " = "
-
-                    let where_value =
-                        if where_value_raw.starts_with('\'') && where_value_raw.ends_with('\'') {
-                            &where_value_raw[1..where_value_raw.len() - 1]
-                        } else {
-                            where_value_raw
-                        };
-
-                    let position = schema_column_names
-                        .iter()
-                        .position(|&schema_col| schema_col == where_column);
-
-                    let position = match position {
-                        Some(pos) => pos,
-                        None => bail!(
-                            "WHERE column '{}' not found in table '{}'",
-                            where_column,
-                            table_name
-                        ),
-                    };
-
-                    (Some(where_column), Some(where_value), Some(position))
-                } else {
-                    (None, None, None)
-                };
-
-            // eprintln!("column_names: {:?}", column_names);
-
-            let mut column_positions: Vec<usize> = Vec::new();
-
-            for &requested_column in &requested_column_names {
-                let position = schema_column_names
-                    .iter()
-                    .position(|&schema_col| schema_col == requested_column);
-
-                let position = match position {
-                    Some(pos) => pos,
-                    None => bail!(
-                        "Column '{}' not found in table '{}'",
-                        requested_column,
-                        table_name
-                    ),
-                };
-
-                column_positions.push(position);
-            }
-
-            // Debug output to verify it works:
-            // println!("Column positions: {:?}", column_positions);
-
-            let all_records = if let (Some(col), Some(val)) = (&where_column, &where_value) {
-                if col.eq_ignore_ascii_case("country") {
-                    // Try to locate index entry in sqlite_schema
-                    let expected_index_name = format!("idx_{}_country", table_name);
-
-                    let index_record_opt = db.root_page.records().find(|record| {
-                        match (&record.values[0], &record.values[1]) {
-                            (RecordValue::Text(t), RecordValue::Text(name)) => {
-                                t == "index" && name.to_lowercase() == expected_index_name
-                            }
-                            _ => false,
-                        }
-                    });
-
-                    if let Some(index_record) = index_record_opt {
-                        // Extract rootpage of index
-                        let index_root = match &index_record.values[3] {
-                            RecordValue::Int(i) => *i as usize,
-                            _ => 0,
-                        };
-
-                        if index_root > 0 {
-                            if let Ok(rowids) =
-                                db.lookup_rowids_by_country(db_path, index_root, val)
-                            {
-                                // Fetch only needed records
-                                if let Ok(recs) =
-                                    db.fetch_records_by_rowids(db_path, rootpage, &rowids)
-                                {
-                                    recs
-                                } else {
-                                    db.get_all_records(db_path, rootpage)?
-                                }
-                            } else {
-                                db.get_all_records(db_path, rootpage)?
-                            }
-                        } else {
-                            db.get_all_records(db_path, rootpage)?
-                        }
-                    } else {
-                        db.get_all_records(db_path, rootpage)?
-                    }
-                } else {
-                    db.get_all_records(db_path, rootpage)?
-                }
-            } else {
-                db.get_all_records(db_path, rootpage)?
-            };
-
-            for record in all_records {
-                // Bounds checking: ensure record has enough columns
-                let max_position = column_positions.iter().max().unwrap_or(&0);
-                if record.values.len() <= *max_position {
-                    bail!(
-                        "Record doesn't have enough columns for position {}",
-                        max_position
-                    );
-                }
-
-                // NEW: WHERE filtering
-                if let (Some(_), Some(where_val), Some(where_pos)) =
-                    (&where_column, &where_value, &where_column_position)
-                {
-                    if !matches_where_condition(&record, *where_pos, where_val) {
-                        continue; // Skip this record
-                    }
-                }
-
-                // Existing output logic (unchanged)
-                let mut row_values: Vec<String> = Vec::new();
-
-                for (i, &position) in column_positions.iter().enumerate() {
-                    let formatted_value = if requested_column_names[i] == "id" && position == 0 {
-                        // Special case: first column named "id" is the rowid
-                        record.id.to_string()
-                    } else {
-                        let column_value = &record.values[position];
-                        format_record_value(column_value)
-                    };
-                    row_values.push(formatted_value);
-                }
-                let row_output = row_values.join("|");
-                println!("{}", row_output);
-            }
+    }
+    let Some(path) = positional.first() else {
+        bail!("Missing <database path>\n{}", USAGE);
+    };
+    let db = Database::load(path)?;
+
+    match positional.get(1) {
+        // A single command leaves the program anyway, `.quit` or not.
+        Some(command) => {
+            let _ = run_command(&db, &mut output, command)?;
         }
-        _ => bail!("Missing or invalid command passed: {}", command),
+        None => shell::run(|command| run_command(&db, &mut output, command))?,
     }
 
     Ok(())
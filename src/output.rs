@@ -0,0 +1,681 @@
+//! # output.rs – printing result rows in the shell's output modes
+//!
+//! ```text
+//!  list      i|t                       csv       i,t
+//!            1|plain                             1,plain
+//!            22|say "hi"                         22,"say ""hi"""
+//!  line          i = 1                 json      [{"i":1,"t":"plain"},
+//!                t = plain                       {"i":22,"t":"say \"hi\""}]
+//!
+//!                i = 22
+//!                t = say "hi"
+//!  column    i   t                     markdown  | i  |    t     |
+//!            --  --------                        |----|----------|
+//!            1   plain                           | 1  | plain    |
+//!            22  say "hi"                        | 22 | say "hi" |
+//!  table     +----+----------+         box       ┌────┬──────────┐
+//!            | i  |    t     |                   │ i  │    t     │
+//!            +----+----------+                   ├────┼──────────┤
+//!            | 1  | plain    |                   │ 1  │ plain    │
+//!            | 22 | say "hi" |                   │ 22 │ say "hi" │
+//!            +----+----------+                   └────┴──────────┘
+//! ```
+//!
+//! That is `SELECT 1 AS i, 'plain' AS t UNION ALL SELECT 22, 'say "hi"'`
+//! with headers on. Every mode prints what the sqlite3 shell prints for it,
+//! NULL as an empty field included. list, csv, json and line write each row
+//! as it comes; column, table, box and markdown need every value to size
+//! their columns, so they hold on to the whole result first.
+//!
+use std::io::{self, Write};
+
+use codecrafters_sqlite::{RecordValue, Rows};
+
+/// How query results are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    List,
+    Csv,
+    Json,
+    Line,
+    Column,
+    Box,
+    Table,
+    Markdown,
+}
+
+impl Mode {
+    const ALL: [Mode; 8] = [
+        Mode::Box,
+        Mode::Column,
+        Mode::Csv,
+        Mode::Json,
+        Mode::Line,
+        Mode::List,
+        Mode::Markdown,
+        Mode::Table,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::List => "list",
+            Mode::Csv => "csv",
+            Mode::Json => "json",
+            Mode::Line => "line",
+            Mode::Column => "column",
+            Mode::Box => "box",
+            Mode::Table => "table",
+            Mode::Markdown => "markdown",
+        }
+    }
+
+    /// Look a mode up by name; the error lists the ones there are.
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        match Self::ALL.into_iter().find(|mode| mode.name() == name) {
+            Some(mode) => Ok(mode),
+            None => {
+                let names: Vec<&str> = Self::ALL.iter().map(|mode| mode.name()).collect();
+                anyhow::bail!("mode should be one of: {}", names.join(" "))
+            }
+        }
+    }
+}
+
+/// The output settings `.mode`, `.headers` and `.separator` change.
+#[derive(Debug)]
+pub struct Output {
+    mode: Mode,
+    headers: bool,
+    /// `.headers` (or `--headers`/`--noheaders`) was used, so column mode
+    /// leaves the choice alone.
+    headers_set: bool,
+    /// Between fields and after each row, in list and csv mode.
+    column_separator: String,
+    row_separator: String,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            mode: Mode::List,
+            headers: false,
+            headers_set: false,
+            column_separator: "|".to_string(),
+            row_separator: "\n".to_string(),
+        }
+    }
+}
+
+impl Output {
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Switch modes the way sqlite3's `.mode` does: list and csv bring back
+    /// their own separators, and column turns headers on unless they were
+    /// switched on or off explicitly.
+    pub fn set_mode(&mut self, mode: Mode) {
+        match mode {
+            Mode::List => self.set_separators("|", Some("\n")),
+            Mode::Csv => self.set_separators(",", Some("\r\n")),
+            Mode::Column if !self.headers_set => self.headers = true,
+            _ => {}
+        }
+        self.mode = mode;
+    }
+
+    /// Whether list, csv and column mode print a header row. table, box and
+    /// markdown always do; json and line name every value anyway.
+    pub fn set_headers(&mut self, headers: bool) {
+        self.headers = headers;
+        self.headers_set = true;
+    }
+
+    pub fn set_separators(&mut self, column: &str, row: Option<&str>) {
+        self.column_separator = column.to_string();
+        if let Some(row) = row {
+            self.row_separator = row.to_string();
+        }
+    }
+
+    /// Print every row of `rows` to `out`. Nothing at all is printed for an
+    /// empty result, not even headers.
+    pub fn print(&self, out: &mut impl Write, rows: Rows<'_>) -> anyhow::Result<()> {
+        let columns = rows.columns().to_vec();
+        self.write_rows(out, &columns, rows.map(|row| Ok(row?.into_values())))
+    }
+
+    // `print` over plain values, so it can be tried without a database.
+    fn write_rows(
+        &self,
+        out: &mut impl Write,
+        columns: &[String],
+        rows: impl Iterator<Item = anyhow::Result<Vec<RecordValue>>>,
+    ) -> anyhow::Result<()> {
+        match self.mode {
+            Mode::List | Mode::Csv => {
+                let field = |value: &RecordValue| match self.mode {
+                    Mode::Csv => csv_field(value, &self.column_separator),
+                    _ => raw(value),
+                };
+                for (i, row) in rows.enumerate() {
+                    let row = row?;
+                    if i == 0 && self.headers {
+                        let names: Vec<RecordValue> =
+                            columns.iter().cloned().map(RecordValue::Text).collect();
+                        self.write_record(out, names.iter().map(field))?;
+                    }
+                    self.write_record(out, row.iter().map(field))?;
+                }
+            }
+            Mode::Json => {
+                let mut any = false;
+                for row in rows {
+                    let row = row?;
+                    out.write_all(if any { b",\n{" } else { b"[{" })?;
+                    any = true;
+                    for (i, (name, value)) in columns.iter().zip(&row).enumerate() {
+                        let separator = if i == 0 { "" } else { "," };
+                        write!(
+                            out,
+                            "{}{}:{}",
+                            separator,
+                            json_string(name),
+                            json_value(value)
+                        )?;
+                    }
+                    out.write_all(b"}")?;
+                }
+                if any {
+                    out.write_all(b"]\n")?;
+                }
+            }
+            Mode::Line => {
+                let width = columns
+                    .iter()
+                    .map(|name| name.chars().count())
+                    .fold(5, usize::max);
+                for (i, row) in rows.enumerate() {
+                    if i > 0 {
+                        out.write_all(self.row_separator.as_bytes())?;
+                    }
+                    for (name, value) in columns.iter().zip(&row?) {
+                        write!(out, "{:>width$} = ", name, width = width)?;
+                        out.write_all(&raw(value))?;
+                        out.write_all(b"\n")?;
+                    }
+                }
+            }
+            Mode::Column | Mode::Box | Mode::Table | Mode::Markdown => {
+                let rows = rows
+                    .map(|row| Ok(row?.iter().map(display).collect()))
+                    .collect::<anyhow::Result<Vec<Vec<String>>>>()?;
+                if !rows.is_empty() {
+                    self.write_grid(out, columns, &rows)?;
+                }
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    fn write_record(
+        &self,
+        out: &mut impl Write,
+        fields: impl Iterator<Item = Vec<u8>>,
+    ) -> io::Result<()> {
+        for (i, field) in fields.enumerate() {
+            if i > 0 {
+                out.write_all(self.column_separator.as_bytes())?;
+            }
+            out.write_all(&field)?;
+        }
+        out.write_all(self.row_separator.as_bytes())
+    }
+
+    // column, table, box and markdown: every column as wide as its widest
+    // line. Values spanning several lines get a line per line, and then
+    // rows are set apart so it's clear where one ends.
+    fn write_grid(
+        &self,
+        out: &mut impl Write,
+        columns: &[String],
+        rows: &[Vec<String>],
+    ) -> io::Result<()> {
+        let cells: Vec<Vec<Vec<String>>> = rows
+            .iter()
+            .map(|row| row.iter().map(|value| cell_lines(value)).collect())
+            .collect();
+        let mut widths: Vec<usize> = columns.iter().map(|name| width(name)).collect();
+        for row in &cells {
+            for (width, lines) in widths.iter_mut().zip(row) {
+                *width = lines
+                    .iter()
+                    .map(|line| self::width(line))
+                    .fold(*width, usize::max);
+            }
+        }
+        let multi_line = cells.iter().flatten().any(|lines| lines.len() > 1);
+
+        // (left edge, between columns, right edge, horizontal line)
+        let (top, middle, bottom, bar) = match self.mode {
+            Mode::Box => (
+                Some(("┌─", "─┬─", "─┐")),
+                Some(("├─", "─┼─", "─┤")),
+                Some(("└─", "─┴─", "─┘")),
+                ("│ ", " │ ", " │", "─"),
+            ),
+            Mode::Table => (
+                Some(("+-", "-+-", "-+")),
+                Some(("+-", "-+-", "-+")),
+                Some(("+-", "-+-", "-+")),
+                ("| ", " | ", " |", "-"),
+            ),
+            Mode::Markdown => (
+                None,
+                Some(("|-", "-|-", "-|")),
+                None,
+                ("| ", " | ", " |", "-"),
+            ),
+            _ => (None, None, None, ("", "  ", "", "-")),
+        };
+        let (left, between, right, dash) = bar;
+        let rule = |out: &mut dyn Write, edges: Option<(&str, &str, &str)>| -> io::Result<()> {
+            let Some((left, between, right)) = edges else {
+                return Ok(());
+            };
+            let lines: Vec<String> = widths.iter().map(|&width| dash.repeat(width)).collect();
+            writeln!(out, "{}{}{}", left, lines.join(between), right)
+        };
+        let line = |out: &mut dyn Write, texts: Vec<String>| -> io::Result<()> {
+            writeln!(out, "{}{}{}", left, texts.join(between), right)
+        };
+
+        rule(out, top)?;
+        // Only column mode can do without a header.
+        if self.mode != Mode::Column || self.headers {
+            let names = columns.iter().zip(&widths).map(|(name, &width)| {
+                if self.mode == Mode::Column {
+                    pad_right(name, width)
+                } else {
+                    centre(name, width)
+                }
+            });
+            line(out, names.collect())?;
+            match self.mode {
+                Mode::Column => {
+                    let underline = widths.iter().map(|&width| dash.repeat(width));
+                    line(out, underline.collect())?;
+                }
+                _ => rule(out, middle)?,
+            }
+        }
+
+        for (i, row) in cells.iter().enumerate() {
+            if i > 0 && multi_line {
+                match self.mode {
+                    Mode::Column => writeln!(out)?,
+                    Mode::Box | Mode::Table => rule(out, middle)?,
+                    _ => {}
+                }
+            }
+            let height = row.iter().map(Vec::len).max().unwrap_or(1);
+            for line_number in 0..height {
+                let texts = row.iter().zip(&widths).map(|(lines, &width)| {
+                    let text = lines.get(line_number).map_or("", String::as_str);
+                    pad_right(text, width)
+                });
+                line(out, texts.collect())?;
+            }
+        }
+        rule(out, bottom)
+    }
+}
+
+// A value as printed: NULL is empty, blobs are their bytes as they are.
+fn raw(value: &RecordValue) -> Vec<u8> {
+    match value {
+        RecordValue::Null => Vec::new(),
+        RecordValue::Blob(bytes) => bytes.clone(),
+        other => other.to_string().into_bytes(),
+    }
+}
+
+// The same as text, for modes that have to measure it.
+fn display(value: &RecordValue) -> String {
+    match value {
+        RecordValue::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+// The lines of a value in a grid cell, with tabs expanded to every 8th
+// column.
+fn cell_lines(text: &str) -> Vec<String> {
+    text.split('\n')
+        .map(|line| {
+            let mut expanded = String::new();
+            for c in line.chars() {
+                if c == '\t' {
+                    let spaces = 8 - width(&expanded) % 8;
+                    expanded.extend(std::iter::repeat(' ').take(spaces));
+                } else {
+                    expanded.push(c);
+                }
+            }
+            expanded
+        })
+        .collect()
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+fn pad_right(text: &str, width: usize) -> String {
+    format!(
+        "{}{}",
+        text,
+        " ".repeat(width.saturating_sub(self::width(text)))
+    )
+}
+
+fn centre(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(self::width(text));
+    let left = padding / 2;
+    format!("{}{}{}", " ".repeat(left), text, " ".repeat(padding - left))
+}
+
+// A csv field. Like sqlite3 this quotes more than RFC 4180 needs: anything
+// empty, or holding the separator, a quote, an apostrophe, whitespace, a
+// control character or non-ASCII. NULL is the one field left empty.
+fn csv_field(value: &RecordValue, separator: &str) -> Vec<u8> {
+    if let RecordValue::Null = value {
+        return Vec::new();
+    }
+    let bytes = raw(value);
+    let needs_quotes = bytes.is_empty()
+        || (!separator.is_empty()
+            && bytes
+                .windows(separator.len())
+                .any(|window| window == separator.as_bytes()))
+        || bytes
+            .iter()
+            .any(|&byte| byte <= b' ' || matches!(byte, b'"' | b'\'') || byte >= 0x7F);
+    if !needs_quotes {
+        return bytes;
+    }
+    let mut quoted = vec![b'"'];
+    for byte in bytes {
+        if byte == b'"' {
+            quoted.push(b'"');
+        }
+        quoted.push(byte);
+    }
+    quoted.push(b'"');
+    quoted
+}
+
+fn json_value(value: &RecordValue) -> String {
+    match value {
+        RecordValue::Null => "null".to_string(),
+        RecordValue::Int(n) => n.to_string(),
+        // The shortest form that reads back as the same double. JSON has no
+        // infinity, so it becomes a number too big for any double.
+        RecordValue::Real(f) if f.is_infinite() => {
+            if *f > 0.0 { "9.0e+999" } else { "-9.0e+999" }.to_string()
+        }
+        RecordValue::Real(f) => format!("{:?}", f),
+        RecordValue::Text(text) => json_string(text),
+        // Each byte as the code point U+0000 to U+00FF, escaped past ASCII
+        // as sqlite3 does.
+        RecordValue::Blob(bytes) => {
+            let latin1: String = bytes.iter().map(|&byte| byte as char).collect();
+            json_string(&latin1)
+                .chars()
+                .map(|c| match c {
+                    c if c.is_ascii() => c.to_string(),
+                    c => format!("\\u{:04x}", c as u32),
+                })
+                .collect()
+        }
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\u{8}' => quoted.push_str("\\b"),
+            '\u{c}' => quoted.push_str("\\f"),
+            c if c.is_control() && c <= '\u{ff}' => {
+                quoted.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_like_sqlite3() {
+        let text = |s: &str| RecordValue::Text(s.to_string());
+        let csv_field = |value: &RecordValue, separator| {
+            String::from_utf8(csv_field(value, separator)).unwrap()
+        };
+        assert_eq!(csv_field(&RecordValue::Null, ","), "");
+        assert_eq!(csv_field(&text(""), ","), "\"\"");
+        assert_eq!(csv_field(&text("plain"), ","), "plain");
+        assert_eq!(csv_field(&RecordValue::Real(2.5), ","), "2.5");
+        assert_eq!(csv_field(&text("a,b"), ","), "\"a,b\"");
+        assert_eq!(csv_field(&text("a;b"), ";"), "\"a;b\"");
+        assert_eq!(csv_field(&text("say \"hi\""), ","), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field(&text("it's"), ","), "\"it's\"");
+        assert_eq!(csv_field(&text("two\nlines"), ","), "\"two\nlines\"");
+        assert_eq!(csv_field(&text("é"), ","), "\"é\"");
+    }
+
+    #[test]
+    fn json_escapes_strings_and_blobs() {
+        assert_eq!(json_string("a\"b\\c\nd\u{1}é"), r#""a\"b\\c\nd\u0001é""#);
+        assert_eq!(
+            json_value(&RecordValue::Blob(vec![0x41, 0x00, 0xFF])),
+            r#""A\u0000\u00ff""#
+        );
+        assert_eq!(json_value(&RecordValue::Real(0.1)), "0.1");
+        assert_eq!(json_value(&RecordValue::Real(f64::INFINITY)), "9.0e+999");
+        assert_eq!(json_value(&RecordValue::Null), "null");
+    }
+
+    fn output(mode: Mode, headers: bool) -> Output {
+        let mut output = Output::default();
+        output.set_mode(mode);
+        output.set_headers(headers);
+        output
+    }
+
+    // What `output` prints for `rows`, as text.
+    fn render(output: &Output, rows: &[(i64, &str)]) -> String {
+        let columns = ["i".to_string(), "t".to_string()];
+        let rows = rows
+            .iter()
+            .map(|&(i, t)| Ok(vec![RecordValue::Int(i), RecordValue::Text(t.to_string())]));
+        let mut out = Vec::new();
+        output.write_rows(&mut out, &columns, rows).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// The example in the module doc.
+    const EXAMPLE: &[(i64, &str)] = &[(1, "plain"), (22, "say \"hi\"")];
+
+    #[test]
+    fn every_mode_prints_the_module_doc_example() {
+        let cases = [
+            (Mode::List, "i|t\n1|plain\n22|say \"hi\"\n"),
+            (Mode::Csv, "i,t\r\n1,plain\r\n22,\"say \"\"hi\"\"\"\r\n"),
+            (
+                Mode::Json,
+                "[{\"i\":1,\"t\":\"plain\"},\n{\"i\":22,\"t\":\"say \\\"hi\\\"\"}]\n",
+            ),
+            (
+                Mode::Line,
+                "    i = 1\n    t = plain\n\n    i = 22\n    t = say \"hi\"\n",
+            ),
+            (
+                Mode::Column,
+                "i   t       \n--  --------\n1   plain   \n22  say \"hi\"\n",
+            ),
+            (
+                Mode::Table,
+                "+----+----------+\n\
+                 | i  |    t     |\n\
+                 +----+----------+\n\
+                 | 1  | plain    |\n\
+                 | 22 | say \"hi\" |\n\
+                 +----+----------+\n",
+            ),
+            (
+                Mode::Box,
+                "┌────┬──────────┐\n\
+                 │ i  │    t     │\n\
+                 ├────┼──────────┤\n\
+                 │ 1  │ plain    │\n\
+                 │ 22 │ say \"hi\" │\n\
+                 └────┴──────────┘\n",
+            ),
+            (
+                Mode::Markdown,
+                "| i  |    t     |\n\
+                 |----|----------|\n\
+                 | 1  | plain    |\n\
+                 | 22 | say \"hi\" |\n",
+            ),
+        ];
+        for (mode, expected) in cases {
+            assert_eq!(render(&output(mode, true), EXAMPLE), expected, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn list_and_csv_headers_and_separators() {
+        assert_eq!(
+            render(&output(Mode::List, false), EXAMPLE),
+            "1|plain\n22|say \"hi\"\n"
+        );
+        assert_eq!(
+            render(&output(Mode::Csv, false), EXAMPLE),
+            "1,plain\r\n22,\"say \"\"hi\"\"\"\r\n"
+        );
+
+        let mut list = output(Mode::List, true);
+        list.set_separators(";", Some("~"));
+        assert_eq!(render(&list, EXAMPLE), "i;t~1;plain~22;say \"hi\"~");
+        // csv quotes fields holding its separator, whatever that is.
+        let mut csv = output(Mode::Csv, false);
+        csv.set_separators("l", None);
+        assert_eq!(
+            render(&csv, EXAMPLE),
+            "1l\"plain\"\r\n22l\"say \"\"hi\"\"\"\r\n"
+        );
+        // Switching back to list brings back its own separators.
+        csv.set_mode(Mode::List);
+        assert_eq!(render(&csv, EXAMPLE), "1|plain\n22|say \"hi\"\n");
+    }
+
+    #[test]
+    fn grids_give_multi_line_cells_a_line_per_line() {
+        let rows = [(1, "two\nlines"), (22, "x")];
+        let cases = [
+            (
+                Mode::Column,
+                true,
+                "i   t    \n--  -----\n1   two  \n    lines\n\n22  x    \n",
+            ),
+            (Mode::Column, false, "1   two  \n    lines\n\n22  x    \n"),
+            (
+                Mode::Table,
+                true,
+                "+----+-------+\n\
+                 | i  |   t   |\n\
+                 +----+-------+\n\
+                 | 1  | two   |\n\
+                 |    | lines |\n\
+                 +----+-------+\n\
+                 | 22 | x     |\n\
+                 +----+-------+\n",
+            ),
+            (
+                Mode::Box,
+                true,
+                "┌────┬───────┐\n\
+                 │ i  │   t   │\n\
+                 ├────┼───────┤\n\
+                 │ 1  │ two   │\n\
+                 │    │ lines │\n\
+                 ├────┼───────┤\n\
+                 │ 22 │ x     │\n\
+                 └────┴───────┘\n",
+            ),
+            (
+                Mode::Markdown,
+                true,
+                "| i  |   t   |\n\
+                 |----|-------|\n\
+                 | 1  | two   |\n\
+                 |    | lines |\n\
+                 | 22 | x     |\n",
+            ),
+        ];
+        for (mode, headers, expected) in cases {
+            assert_eq!(
+                render(&output(mode, headers), &rows),
+                expected,
+                "{:?}",
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn an_empty_result_prints_nothing() {
+        for mode in Mode::ALL {
+            assert_eq!(render(&output(mode, true), &[]), "", "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn column_mode_turns_headers_on_unless_they_were_set() {
+        let mut output = Output::default();
+        output.set_mode(Mode::Column);
+        assert!(output.headers);
+
+        let mut output = Output::default();
+        output.set_headers(false);
+        output.set_mode(Mode::Column);
+        assert!(!output.headers);
+
+        // Once on, they stay on in other modes, like in sqlite3.
+        let mut output = Output::default();
+        output.set_mode(Mode::Column);
+        output.set_mode(Mode::List);
+        assert!(output.headers);
+    }
+
+    #[test]
+    fn grid_cells_split_lines_and_expand_tabs() {
+        assert_eq!(cell_lines("tab\tx"), ["tab     x"]);
+        assert_eq!(cell_lines("one\ntwo"), ["one", "two"]);
+        assert_eq!(centre("r", 7), "   r   ");
+        assert_eq!(centre("ab", 5), " ab  ");
+    }
+}
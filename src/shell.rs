@@ -0,0 +1,285 @@
+//! # shell.rs – the interactive prompt
+//!
+//! ```text
+//!  sqlite> SELECT name
+//!     ...>   FROM apples;         ← SQL runs once a `;` ends it
+//!  sqlite> .tables                ← dot-commands run at the end of the line
+//! ```
+//!
+//! Lines pile up in a buffer until it holds a `;` outside of any string,
+//! quoted name or comment; every statement finished that way runs, and
+//! whatever follows the last `;` waits for more input. A line starting with
+//! `.` on an empty buffer is a dot-command and needs no `;`.
+//!
+//! On a terminal the prompt has line editing and keeps its history in
+//! `~/.codecrafters_sqlite_history`. Piped input is read line by line with
+//! no prompt, so a script can be fed in on stdin. Errors are printed and the
+//! shell carries on; `.quit`, `.exit` or end of input leave it.
+//!
+use std::{
+    io::{self, BufRead, IsTerminal},
+    ops::ControlFlow,
+    path::PathBuf,
+};
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+const PROMPT: &str = "sqlite> ";
+const CONTINUATION_PROMPT: &str = "   ...> ";
+const HISTORY_FILE: &str = ".codecrafters_sqlite_history";
+
+/// What one read from the input produced.
+enum Line {
+    Text(String),
+    /// Ctrl-C: drop the statement being typed.
+    Cancelled,
+    End,
+}
+
+/// Where lines come from: an editor on a terminal, plain stdin otherwise.
+enum Input {
+    Terminal {
+        editor: Box<DefaultEditor>,
+        history: Option<PathBuf>,
+    },
+    Piped(io::StdinLock<'static>),
+}
+
+impl Input {
+    fn open() -> anyhow::Result<Self> {
+        if !io::stdin().is_terminal() {
+            return Ok(Input::Piped(io::stdin().lock()));
+        }
+        let mut editor = Box::new(DefaultEditor::new()?);
+        let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        if let Some(path) = &history {
+            // There's no history yet the first time round.
+            let _ = editor.load_history(path);
+        }
+        Ok(Input::Terminal { editor, history })
+    }
+
+    fn read_line(&mut self, prompt: &str) -> anyhow::Result<Line> {
+        match self {
+            Input::Terminal { editor, .. } => match editor.readline(prompt) {
+                Ok(line) => Ok(Line::Text(line)),
+                Err(ReadlineError::Interrupted) => Ok(Line::Cancelled),
+                Err(ReadlineError::Eof) => Ok(Line::End),
+                Err(err) => Err(err.into()),
+            },
+            Input::Piped(stdin) => {
+                let mut line = String::new();
+                if stdin.read_line(&mut line)? == 0 {
+                    return Ok(Line::End);
+                }
+                Ok(Line::Text(line.trim_end_matches(['\n', '\r']).to_string()))
+            }
+        }
+    }
+
+    fn remember(&mut self, entry: &str) {
+        if let Input::Terminal { editor, .. } = self {
+            let _ = editor.add_history_entry(entry.trim());
+        }
+    }
+
+    fn save_history(&mut self) {
+        if let Input::Terminal {
+            editor,
+            history: Some(path),
+        } = self
+        {
+            if let Err(err) = editor.save_history(path) {
+                eprintln!(
+                    "warning: could not save history to {}: {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+/// Read commands and statements until `.quit`/`.exit` or the end of input,
+/// handing each one to `execute`. `execute` breaks to leave the shell;
+/// its errors are printed and the shell keeps going.
+pub fn run(mut execute: impl FnMut(&str) -> anyhow::Result<ControlFlow<()>>) -> anyhow::Result<()> {
+    let mut input = Input::open()?;
+    let mut buffer = String::new();
+    let mut run_one = |command: &str| match execute(command) {
+        Ok(flow) => flow,
+        Err(err) => {
+            eprintln!("Error: {:#}", err);
+            ControlFlow::Continue(())
+        }
+    };
+
+    loop {
+        let prompt = if buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        let line = match input.read_line(prompt)? {
+            Line::Text(line) => line,
+            Line::Cancelled => {
+                buffer.clear();
+                continue;
+            }
+            Line::End => break,
+        };
+
+        if buffer.is_empty() && line.trim_start().starts_with('.') {
+            input.remember(&line);
+            if run_one(line.trim()).is_break() {
+                break;
+            }
+            continue;
+        }
+
+        buffer.push_str(&line);
+        buffer.push('\n');
+        let pending = buffer.clone();
+        let statements = take_statements(&mut buffer);
+        if !statements.is_empty() {
+            input.remember(&pending[..pending.len() - buffer.len()]);
+        }
+        if statements.iter().any(|sql| run_one(sql).is_break()) {
+            break;
+        }
+    }
+
+    // Like sqlite3, run what's left of a script that forgot its last `;`.
+    let rest = buffer.trim();
+    if !rest.is_empty() {
+        let _ = run_one(rest);
+    }
+    input.save_history();
+    Ok(())
+}
+
+/// Split the finished statements off the front of `buffer`: the text before
+/// each `;` that isn't inside a string, quoted name or comment, without the
+/// `;`. Empty statements are dropped. What's left after the last `;` stays
+/// in `buffer`, unless it's only whitespace and comments.
+fn take_statements(buffer: &mut String) -> Vec<String> {
+    let bytes = buffer.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    // Nothing but whitespace and comments since `start`.
+    let mut blank = true;
+    // The input ends inside a quote or a block comment.
+    let mut unterminated = false;
+
+    // Index just past the first `close` at or after `from`.
+    let skip_past = |from: usize, close: &[u8]| {
+        bytes[from.min(bytes.len())..]
+            .windows(close.len())
+            .position(|window| window == close)
+            .map(|at| from + at + close.len())
+    };
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let next = match bytes[i] {
+            b';' => {
+                if !blank {
+                    statements.push(buffer[start..i].trim().to_string());
+                }
+                start = i + 1;
+                blank = true;
+                i + 1
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                skip_past(i + 2, b"\n").unwrap_or(bytes.len())
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => match skip_past(i + 2, b"*/") {
+                Some(end) => end,
+                None => {
+                    unterminated = true;
+                    bytes.len()
+                }
+            },
+            quote @ (b'\'' | b'"' | b'`' | b'[') => {
+                blank = false;
+                let close = if quote == b'[' { b']' } else { quote };
+                // A doubled quote inside a string closes and reopens it,
+                // which splits the same way.
+                match skip_past(i + 1, &[close]) {
+                    Some(end) => end,
+                    None => {
+                        unterminated = true;
+                        bytes.len()
+                    }
+                }
+            }
+            byte => {
+                if !byte.is_ascii_whitespace() {
+                    blank = false;
+                }
+                i + 1
+            }
+        };
+        i = next;
+    }
+
+    if blank && !unterminated {
+        buffer.clear();
+    } else {
+        buffer.drain(..start);
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(input: &str) -> (Vec<String>, String) {
+        let mut buffer = input.to_string();
+        let statements = take_statements(&mut buffer);
+        (statements, buffer)
+    }
+
+    #[test]
+    fn waits_for_the_semicolon() {
+        assert_eq!(split("SELECT 1\n"), (vec![], "SELECT 1\n".to_string()));
+        assert_eq!(
+            split("SELECT 1\nFROM t;\n"),
+            (vec!["SELECT 1\nFROM t".to_string()], String::new())
+        );
+    }
+
+    #[test]
+    fn splits_several_statements_and_keeps_the_rest() {
+        assert_eq!(
+            split("SELECT 1; ; SELECT 2;SELECT\n"),
+            (
+                vec!["SELECT 1".to_string(), "SELECT 2".to_string()],
+                "SELECT\n".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn ignores_semicolons_in_quotes_and_comments() {
+        let sql = "SELECT 'a;b', \"c;d\", [e;f] -- g;\n/* h; */ FROM t;";
+        assert_eq!(
+            split(sql),
+            (vec![sql[..sql.len() - 1].to_string()], String::new())
+        );
+        assert_eq!(
+            split("SELECT 'it''s;"),
+            (vec![], "SELECT 'it''s;".to_string())
+        );
+    }
+
+    #[test]
+    fn drops_comments_and_whitespace_between_statements() {
+        assert_eq!(split("  -- just a comment\n"), (vec![], String::new()));
+        assert_eq!(
+            split("/* not closed\n"),
+            (vec![], "/* not closed\n".to_string())
+        );
+    }
+}
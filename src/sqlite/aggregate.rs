@@ -0,0 +1,374 @@
+//! # sqlite/aggregate.rs – fold many rows into one value
+//!
+//! ```text
+//!  row, row, row ──step()──▶ Accumulator ──finish()──▶ RecordValue
+//!                             (count, running sum, min so far, …)
+//! ```
+//!
+//! Follows SQLite's rules:
+//! – every aggregate but `count(*)` ignores NULL arguments;
+//! – `sum()` stays an integer while every input is one (and fails on
+//!   overflow), `avg()` and `total()` are always real;
+//! – over zero rows `count()` is 0 and `total()` is 0.0, everything else
+//!   is NULL.
+//!
+
+use super::error::{QueryError, Result};
+use super::eval::{self, Affinity, Scope};
+use super::sql::Expr;
+use super::RecordValue;
+
+const AGGREGATES: &[&str] = &["avg", "count", "group_concat", "max", "min", "sum", "total"];
+
+/// Is `expr` a call to an aggregate function? `min`/`max` with several
+/// arguments are the scalar functions of the same name, not aggregates.
+pub fn is_aggregate(expr: &Expr) -> bool {
+    let Expr::Function { name, args, .. } = expr else {
+        return false;
+    };
+    let name = name.to_ascii_lowercase();
+    AGGREGATES.contains(&name.as_str()) && !(matches!(&*name, "min" | "max") && args.len() > 1)
+}
+
+/// Append every distinct aggregate call in `expr` to `found`, in the order
+/// they first appear.
+pub fn collect_aggregates(expr: &Expr, found: &mut Vec<Expr>) {
+    if is_aggregate(expr) {
+        if !found.contains(expr) {
+            found.push(expr.clone());
+        }
+        return;
+    }
+    match expr {
+        Expr::Literal(_) | Expr::Column { .. } => {}
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => collect_aggregates(expr, found),
+        Expr::Binary { left, right, .. } => {
+            collect_aggregates(left, found);
+            collect_aggregates(right, found);
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            collect_aggregates(expr, found);
+            collect_aggregates(low, found);
+            collect_aggregates(high, found);
+        }
+        Expr::Function { args, .. } => {
+            for arg in args {
+                collect_aggregates(arg, found);
+            }
+        }
+    }
+}
+
+/// Running state of one aggregate call over a group of rows.
+#[derive(Debug, Clone)]
+pub struct Accumulator {
+    args: Vec<Expr>,
+    state: State,
+}
+
+#[derive(Debug, Clone)]
+enum State {
+    CountRows(i64),
+    Count(i64),
+    Sum(Sum),
+    Avg(Sum),
+    Total(Sum),
+    Min(Option<RecordValue>),
+    Max(Option<RecordValue>),
+    GroupConcat(Option<String>),
+}
+
+impl Accumulator {
+    /// Set up an accumulator for an aggregate call such as `sum(price)`.
+    pub fn new(call: &Expr) -> Result<Self> {
+        let Expr::Function { name, args, star } = call else {
+            return Err(
+                QueryError::Unsupported(format!("not an aggregate call: {:?}", call)).into(),
+            );
+        };
+        let mut nested = Vec::new();
+        for arg in args {
+            collect_aggregates(arg, &mut nested);
+        }
+        if !nested.is_empty() {
+            return Err(QueryError::MisusedAggregate(name.clone()).into());
+        }
+
+        let lower = name.to_ascii_lowercase();
+        let state = match (lower.as_str(), *star, args.len()) {
+            ("count", true, 0) => State::CountRows(0),
+            ("count", false, 1) => State::Count(0),
+            ("sum", false, 1) => State::Sum(Sum::default()),
+            ("avg", false, 1) => State::Avg(Sum::default()),
+            ("total", false, 1) => State::Total(Sum::default()),
+            ("min", false, 1) => State::Min(None),
+            ("max", false, 1) => State::Max(None),
+            ("group_concat", false, 1 | 2) => State::GroupConcat(None),
+            _ => return Err(QueryError::WrongArgumentCount(name.clone()).into()),
+        };
+        Ok(Self {
+            args: args.clone(),
+            state,
+        })
+    }
+
+    /// Does this aggregate pick one row of the group (`min`/`max`)? Bare
+    /// columns next to a single such aggregate come from that row.
+    pub fn selects_row(&self) -> bool {
+        matches!(self.state, State::Min(_) | State::Max(_))
+    }
+
+    /// Fold in one row. Returns `true` when `min`/`max` took a new value
+    /// from this row.
+    pub fn step(&mut self, row: &dyn Scope) -> Result<bool> {
+        if let State::CountRows(count) = &mut self.state {
+            *count += 1;
+            return Ok(false);
+        }
+
+        let value = eval::evaluate(&self.args[0], row)?;
+        if matches!(value, RecordValue::Null) {
+            return Ok(false);
+        }
+
+        match &mut self.state {
+            State::CountRows(_) => unreachable!(),
+            State::Count(count) => *count += 1,
+            State::Sum(sum) | State::Avg(sum) | State::Total(sum) => sum.add(value),
+            State::Min(best) => {
+                // Ties keep the earlier row.
+                if best
+                    .as_ref()
+                    .map_or(true, |b| value.sqlite_cmp_in(b, row.encoding()).is_lt())
+                {
+                    *best = Some(value);
+                    return Ok(true);
+                }
+            }
+            State::Max(best) => {
+                if best
+                    .as_ref()
+                    .map_or(true, |b| value.sqlite_cmp_in(b, row.encoding()).is_gt())
+                {
+                    *best = Some(value);
+                    return Ok(true);
+                }
+            }
+            State::GroupConcat(text) => {
+                let separator = match self.args.get(1) {
+                    Some(expr) => match eval::evaluate(expr, row)? {
+                        RecordValue::Null => String::new(),
+                        other => to_text(other),
+                    },
+                    None => ",".to_string(),
+                };
+                match text {
+                    Some(text) => {
+                        text.push_str(&separator);
+                        text.push_str(&to_text(value));
+                    }
+                    None => *text = Some(to_text(value)),
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// The aggregate's value over every row stepped so far.
+    pub fn finish(&self) -> Result<RecordValue> {
+        Ok(match &self.state {
+            State::CountRows(count) | State::Count(count) => RecordValue::Int(*count),
+            State::Sum(sum) if sum.count == 0 => RecordValue::Null,
+            State::Sum(sum) if sum.overflow => return Err(QueryError::IntegerOverflow.into()),
+            State::Sum(sum) if sum.approx => RecordValue::Real(sum.real()),
+            State::Sum(sum) => RecordValue::Int(sum.int),
+            State::Avg(sum) if sum.count == 0 => RecordValue::Null,
+            State::Avg(sum) => RecordValue::Real(sum.real() / sum.count as f64),
+            State::Total(sum) => RecordValue::Real(sum.real()),
+            State::Min(best) | State::Max(best) => best.clone().unwrap_or(RecordValue::Null),
+            State::GroupConcat(text) => text.clone().map_or(RecordValue::Null, RecordValue::Text),
+        })
+    }
+}
+
+/// Running sum shared by `sum`, `avg` and `total`: an exact integer until
+/// a real shows up (or the integer overflows), a compensated real after.
+#[derive(Debug, Clone, Default)]
+struct Sum {
+    count: i64,
+    int: i64,
+    approx: bool,
+    overflow: bool,
+    real: f64,
+    /// Kahan-Babuska-Neumaier error term, like SQLite keeps.
+    error: f64,
+}
+
+impl Sum {
+    fn add(&mut self, value: RecordValue) {
+        self.count += 1;
+        // Text that looks like an integer counts as one; anything else is
+        // read as a real from its numeric prefix.
+        match Affinity::Numeric.apply(value) {
+            RecordValue::Int(n) if !self.approx => match self.int.checked_add(n) {
+                Some(int) => self.int = int,
+                None => {
+                    self.overflow = true;
+                    self.switch_to_real();
+                    self.add_real(n as f64);
+                }
+            },
+            RecordValue::Int(n) => self.add_real(n as f64),
+            RecordValue::Real(f) => {
+                self.switch_to_real();
+                self.add_real(f);
+            }
+            other => {
+                let f = match eval::to_numeric(&other) {
+                    RecordValue::Int(n) => n as f64,
+                    RecordValue::Real(f) => f,
+                    _ => 0.0,
+                };
+                self.switch_to_real();
+                self.add_real(f);
+            }
+        }
+    }
+
+    fn switch_to_real(&mut self) {
+        if !self.approx {
+            self.approx = true;
+            self.real = self.int as f64;
+        }
+    }
+
+    fn add_real(&mut self, f: f64) {
+        let total = self.real + f;
+        if self.real.abs() >= f.abs() {
+            self.error += (self.real - total) + f;
+        } else {
+            self.error += (f - total) + self.real;
+        }
+        self.real = total;
+    }
+
+    fn real(&self) -> f64 {
+        if self.approx {
+            self.real + self.error
+        } else {
+            self.int as f64
+        }
+    }
+}
+
+fn to_text(value: RecordValue) -> String {
+    match value {
+        RecordValue::Null => String::new(),
+        RecordValue::Int(n) => n.to_string(),
+        RecordValue::Real(f) => eval::format_real(f),
+        RecordValue::Text(text) => text,
+        RecordValue::Blob(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::sql::{self, ResultColumn, Statement};
+    use super::*;
+    use RecordValue::{Int, Null, Real, Text};
+
+    /// A one-column row, `x`.
+    struct X(RecordValue);
+
+    impl Scope for X {
+        fn column(&self, _table: Option<&str>, _name: &str) -> Result<RecordValue> {
+            Ok(self.0.clone())
+        }
+    }
+
+    /// Run the aggregate `call` over rows with the given values of `x`.
+    fn fold(call: &str, xs: &[RecordValue]) -> Result<RecordValue> {
+        let Ok(Statement::Select(select)) = sql::parse(&format!("SELECT {} FROM t", call)) else {
+            panic!("did not parse: {}", call);
+        };
+        let ResultColumn::Expr { expr, .. } = &select.columns[0] else {
+            panic!("not an expression: {}", call);
+        };
+        let mut accumulator = Accumulator::new(expr)?;
+        for x in xs {
+            accumulator.step(&X(x.clone()))?;
+        }
+        accumulator.finish()
+    }
+
+    #[test]
+    fn sum_fails_on_integer_overflow_but_total_and_avg_do_not() {
+        let xs = [Int(i64::MAX), Int(1)];
+        let error = fold("sum(x)", &xs).unwrap_err();
+        assert_eq!(error.to_string(), "integer overflow");
+        assert_eq!(fold("total(x)", &xs).unwrap(), Real(9.223372036854776e18));
+        assert_eq!(fold("avg(x)", &xs).unwrap(), Real(4.611686018427388e18));
+
+        // Once a real has shown up the sum is approximate and can't overflow.
+        let xs = [Real(0.5), Int(i64::MAX), Int(1)];
+        assert_eq!(fold("sum(x)", &xs).unwrap(), Real(9.223372036854776e18));
+    }
+
+    #[test]
+    fn sum_stays_integer_until_a_real_shows_up() {
+        assert_eq!(fold("sum(x)", &[Int(1), Text("2".into())]).unwrap(), Int(3));
+        assert_eq!(
+            fold("sum(x)", &[Int(1), Text("3".into()), Real(2.5)]).unwrap(),
+            Real(6.5)
+        );
+        assert_eq!(fold("sum(x)", &[Text("1.5".into())]).unwrap(), Real(1.5));
+        assert_eq!(fold("avg(x)", &[Int(1), Int(2)]).unwrap(), Real(1.5));
+        assert_eq!(fold("total(x)", &[Int(1), Int(2)]).unwrap(), Real(3.0));
+    }
+
+    #[test]
+    fn empty_input_gives_zero_for_total_and_count_and_null_otherwise() {
+        let cases = [
+            ("total(x)", Real(0.0)),
+            ("count(x)", Int(0)),
+            ("count(*)", Int(0)),
+            ("sum(x)", Null),
+            ("avg(x)", Null),
+            ("min(x)", Null),
+            ("group_concat(x)", Null),
+        ];
+        for (call, expected) in cases {
+            assert_eq!(fold(call, &[]).unwrap(), expected, "{}", call);
+        }
+        // Only NULLs is the same as no rows, except for count(*).
+        assert_eq!(fold("sum(x)", &[Null, Null]).unwrap(), Null);
+        assert_eq!(fold("total(x)", &[Null]).unwrap(), Real(0.0));
+        assert_eq!(fold("count(*)", &[Null, Null]).unwrap(), Int(2));
+    }
+
+    #[test]
+    fn count_of_a_column_skips_nulls() {
+        let xs = [Int(1), Null, Text("b".into()), Null];
+        assert_eq!(fold("count(x)", &xs).unwrap(), Int(2));
+        assert_eq!(fold("count(*)", &xs).unwrap(), Int(4));
+    }
+
+    #[test]
+    fn group_concat_joins_with_a_comma_or_the_given_separator() {
+        let xs = [Int(1), Null, Text("b".into()), Real(2.5)];
+        assert_eq!(
+            fold("group_concat(x)", &xs).unwrap(),
+            Text("1,b,2.5".into())
+        );
+        assert_eq!(
+            fold("group_concat(x, '; ')", &xs).unwrap(),
+            Text("1; b; 2.5".into())
+        );
+        assert_eq!(
+            fold("group_concat(x, NULL)", &xs).unwrap(),
+            Text("1b2.5".into())
+        );
+    }
+}
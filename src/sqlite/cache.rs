@@ -0,0 +1,149 @@
+//! # sqlite/cache.rs – a small LRU cache of raw pages
+//!
+//! ```text
+//!  page_data(n) ──▶ cache hit?  ── yes ──▶ Rc<[u8]>
+//!                      │ no
+//!                      ▼
+//!                    read ──▶ insert (evict least recently used)
+//! ```
+//!
+//! Only the bytes of each page are kept, B-tree and overflow pages alike,
+//! so the cache never holds more than `capacity` pages' worth of memory.
+//! Decoding a B-tree page from its bytes is cheap and happens on every
+//! `Database::load_page`.
+//!
+//! Every access gets a fresh "tick"; `by_tick` keeps ticks sorted so the
+//! oldest entry is always the first key.
+//!
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+/// Hit/miss counters, handy for checking that a query isn't thrashing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct PageCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<usize, (Rc<[u8]>, u64)>,
+    by_tick: BTreeMap<u64, usize>,
+    stats: CacheStats,
+}
+
+impl PageCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            by_tick: BTreeMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, page_number: usize) -> Option<Rc<[u8]>> {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.entries.get_mut(&page_number) {
+            Some((page, last_used)) => {
+                self.by_tick.remove(last_used);
+                self.by_tick.insert(tick, page_number);
+                *last_used = tick;
+                self.stats.hits += 1;
+                Some(Rc::clone(page))
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub(crate) fn insert(&mut self, page_number: usize, page: Rc<[u8]>) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity && !self.entries.contains_key(&page_number) {
+            let Some((_, oldest)) = self.by_tick.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.tick += 1;
+        if let Some((_, old_tick)) = self.entries.insert(page_number, (page, self.tick)) {
+            self.by_tick.remove(&old_tick);
+        }
+        self.by_tick.insert(self.tick, page_number);
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(byte: u8) -> Rc<[u8]> {
+        vec![byte; 4].into()
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_page() {
+        let mut cache = PageCache::new(3);
+        cache.insert(1, page(1));
+        cache.insert(2, page(2));
+        cache.insert(3, page(3));
+
+        // Touching page 1 makes page 2 the oldest.
+        assert!(cache.get(1).is_some());
+        cache.insert(4, page(4));
+        assert!(cache.get(2).is_none());
+        assert_eq!(cache.get(1).as_deref(), Some(&[1; 4][..]));
+
+        // Now 3 is the oldest, then 4, then 1.
+        cache.insert(5, page(5));
+        assert!(cache.get(3).is_none());
+        cache.insert(6, page(6));
+        assert!(cache.get(4).is_none());
+        for kept in [1, 5, 6] {
+            assert!(cache.get(kept).is_some(), "page {} was evicted", kept);
+        }
+    }
+
+    #[test]
+    fn reinserting_a_page_replaces_it_without_evicting_others() {
+        let mut cache = PageCache::new(2);
+        cache.insert(1, page(1));
+        cache.insert(2, page(2));
+        cache.get(1);
+        // Page 2 is the oldest, but the cache isn't growing.
+        cache.insert(1, page(9));
+        assert_eq!(cache.get(1).as_deref(), Some(&[9; 4][..]));
+        assert!(cache.get(2).is_some());
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let mut cache = PageCache::new(2);
+        assert!(cache.get(1).is_none());
+        cache.insert(1, page(1));
+        cache.get(1);
+        cache.get(1);
+        cache.get(2);
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2 });
+    }
+
+    #[test]
+    fn zero_capacity_caches_nothing() {
+        let mut cache = PageCache::new(0);
+        cache.insert(1, page(1));
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 1 });
+    }
+}
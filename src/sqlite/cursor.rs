@@ -0,0 +1,619 @@
+//! # sqlite/cursor.rs – walk a table B-tree one row at a time
+//!
+//! ```text
+//!  stack (root first):
+//!   [ (interior p2, child 1) ]──▶[ (interior p7, child 0) ]──▶[ (leaf p9, cell 3) ]
+//!                                                                   ▲
+//!                                                             current row
+//! ```
+//!
+//! Instead of recursing, the cursor keeps the path from the root to the
+//! current row on an explicit stack of `(page, index)` pairs. For interior
+//! pages the index is the child we descended into (`cell_count()` means the
+//! right-most child), for the leaf it is the current cell. Moving to the
+//! next row bumps the leaf index, and when a leaf runs out we pop up until
+//! some ancestor has another child to descend into. Memory use is one page
+//! per tree level, no matter how big the table is.
+//!
+//! Rowids are signed 64-bit integers, as in SQLite: the tree keeps them in
+//! signed order, and `seek`, `range` and `Record::id` all take and give
+//! `i64`.
+//!
+//! `IndexCursor` does the same for an index B-tree, where interior pages
+//! hold entries of their own between their children, and hands out the
+//! rowids of the entries that start with a given key prefix.
+//!
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+use super::db::{Database, IndexEntry, Page, PageType, Record, RecordValue};
+use super::error::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Not positioned yet: `next` starts at the first row, `prev` at the last.
+    Unpositioned,
+    /// The stack points at a row.
+    Valid,
+    /// Walked off either end.
+    Done,
+}
+
+/// Streaming cursor over the rows of one table, in rowid order.
+///
+/// Iterating it yields rows from the current position onwards; `first`,
+/// `seek_last` and `seek` reposition it, `prev` walks backwards.
+pub struct TableCursor<'db> {
+    db: &'db Database,
+    root_page: usize,
+    stack: Vec<(Page, usize)>,
+    state: State,
+}
+
+impl<'db> TableCursor<'db> {
+    pub fn new(db: &'db Database, root_page: usize) -> Self {
+        Self {
+            db,
+            root_page,
+            stack: Vec::new(),
+            state: State::Unpositioned,
+        }
+    }
+
+    /// Move to the row with the smallest rowid.
+    pub fn first(&mut self) -> Option<Result<Record>> {
+        self.reposition(|cursor| {
+            cursor.descend_leftmost(cursor.root_page)?;
+            cursor.skip_forward()
+        })
+    }
+
+    /// Move to the row with the largest rowid. (Not called `last`: that
+    /// name belongs to `Iterator::last`, which would consume the cursor.)
+    pub fn seek_last(&mut self) -> Option<Result<Record>> {
+        self.reposition(|cursor| {
+            cursor.descend_rightmost(cursor.root_page)?;
+            cursor.step_back()
+        })
+    }
+
+    /// Move to the first row whose rowid is `>= rowid`; compare the
+    /// returned row's id to tell an exact hit from the next one up.
+    pub fn seek(&mut self, rowid: i64) -> Option<Result<Record>> {
+        self.reposition(|cursor| {
+            let mut page_number = cursor.root_page;
+            loop {
+                let page = cursor.load_table_page(page_number)?;
+                if page.is_leaf() {
+                    let index = (0..page.cell_count())
+                        .find(|&i| page.rowid_at(i) >= rowid)
+                        .unwrap_or(page.cell_count());
+                    cursor.stack.push((page, index));
+                    return cursor.skip_forward();
+                }
+                // First cell whose key is >= rowid; past the last key the
+                // row can only be in the right-most child.
+                let mut index = page.cell_count();
+                for i in 0..page.cell_count() {
+                    if rowid <= page.table_key(i)? {
+                        index = i;
+                        break;
+                    }
+                }
+                page_number = page.table_child(index)?;
+                cursor.stack.push((page, index));
+            }
+        })
+    }
+
+    /// The rows whose rowids fall in `rowids`, in order: seeks to the lower
+    /// bound, then stops at the first row past the upper one.
+    ///
+    /// ```no_run
+    /// # fn main() -> codecrafters_sqlite::Result<()> {
+    /// # let db = codecrafters_sqlite::Database::load("sample.db")?;
+    /// for record in db.table_cursor(2).range(1000..=2000) {
+    ///     println!("{}", record?.id);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn range(mut self, rowids: impl RangeBounds<i64>) -> RowidRange<'db> {
+        let first = match rowids.start_bound() {
+            Bound::Included(&start) => self.seek(start),
+            Bound::Excluded(&start) => match start.checked_add(1) {
+                Some(start) => self.seek(start),
+                None => None,
+            },
+            Bound::Unbounded => self.first(),
+        };
+        RowidRange {
+            cursor: self,
+            first: Some(first),
+            end: rowids.end_bound().cloned(),
+        }
+    }
+
+    /// Step back one row. On a fresh cursor this is the last row.
+    pub fn prev(&mut self) -> Option<Result<Record>> {
+        match self.state {
+            State::Unpositioned => self.seek_last(),
+            State::Done => None,
+            State::Valid => self.settle(Self::step_back),
+        }
+    }
+
+    // ---------------- Movement helpers ----------------
+
+    /// Forget the current position, run `locate`, and report the row it
+    /// lands on.
+    fn reposition(
+        &mut self,
+        locate: impl FnOnce(&mut Self) -> Result<bool>,
+    ) -> Option<Result<Record>> {
+        self.stack.clear();
+        self.settle(locate)
+    }
+
+    /// Run a movement that returns whether it landed on a row, and turn the
+    /// outcome into the iterator-style result.
+    fn settle(
+        &mut self,
+        movement: impl FnOnce(&mut Self) -> Result<bool>,
+    ) -> Option<Result<Record>> {
+        match movement(self) {
+            Ok(true) => match self.current() {
+                Ok(record) => {
+                    self.state = State::Valid;
+                    Some(Ok(record))
+                }
+                Err(err) => {
+                    self.finish();
+                    Some(Err(err))
+                }
+            },
+            Ok(false) => {
+                self.finish();
+                None
+            }
+            Err(err) => {
+                self.finish();
+                Some(Err(err))
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.stack.clear();
+        self.state = State::Done;
+    }
+
+    fn current(&self) -> Result<Record> {
+        let (leaf, index) = self.stack.last().expect("cursor is positioned");
+        leaf.record_at(*index, self.db)
+    }
+
+    fn load_table_page(&self, page_number: usize) -> Result<Page> {
+        let page = self.db.load_page(page_number)?;
+        if !matches!(page.typ, PageType::TableLeaf | PageType::TableInterior) {
+            return Err(page.wrong_type("table"));
+        }
+        Ok(page)
+    }
+
+    /// Push the path to the left-most leaf under `page_number`, at cell 0.
+    fn descend_leftmost(&mut self, mut page_number: usize) -> Result<()> {
+        loop {
+            let page = self.load_table_page(page_number)?;
+            if page.is_leaf() {
+                self.stack.push((page, 0));
+                return Ok(());
+            }
+            page_number = page.table_child(0)?;
+            self.stack.push((page, 0));
+        }
+    }
+
+    /// Push the path to the right-most leaf under `page_number`, positioned
+    /// one past its last cell (so `step_back` lands on the last row).
+    fn descend_rightmost(&mut self, mut page_number: usize) -> Result<()> {
+        loop {
+            let page = self.load_table_page(page_number)?;
+            let index = page.cell_count();
+            if page.is_leaf() {
+                self.stack.push((page, index));
+                return Ok(());
+            }
+            page_number = page.table_child(index)?;
+            self.stack.push((page, index));
+        }
+    }
+
+    /// If the leaf index has run past the end, climb to the next subtree
+    /// to the right and descend into it. `false` once the tree is exhausted.
+    fn skip_forward(&mut self) -> Result<bool> {
+        loop {
+            match self.stack.last() {
+                None => return Ok(false),
+                Some((leaf, index)) if *index < leaf.cell_count() => return Ok(true),
+                Some(_) => {}
+            }
+            // This leaf is used up (or empty).
+            self.stack.pop();
+            loop {
+                let Some((parent, index)) = self.stack.last_mut() else {
+                    return Ok(false);
+                };
+                if *index < parent.cell_count() {
+                    *index += 1;
+                    let child = parent.table_child(*index)?;
+                    self.descend_leftmost(child)?;
+                    break;
+                }
+                self.stack.pop();
+            }
+        }
+    }
+
+    /// Move the leaf index back one cell, climbing to the next subtree to
+    /// the left when the leaf has no earlier cells. `false` once there are
+    /// no earlier rows.
+    fn step_back(&mut self) -> Result<bool> {
+        loop {
+            match self.stack.last_mut() {
+                None => return Ok(false),
+                Some((_, index)) if *index > 0 => {
+                    *index -= 1;
+                    return Ok(true);
+                }
+                Some(_) => {}
+            }
+            self.stack.pop();
+            loop {
+                let Some((parent, index)) = self.stack.last_mut() else {
+                    return Ok(false);
+                };
+                if *index > 0 {
+                    *index -= 1;
+                    let child = parent.table_child(*index)?;
+                    self.descend_rightmost(child)?;
+                    break;
+                }
+                self.stack.pop();
+            }
+        }
+    }
+
+    /// Advance one row forward.
+    fn step_forward(&mut self) -> Result<bool> {
+        if let Some((_, index)) = self.stack.last_mut() {
+            *index += 1;
+        }
+        self.skip_forward()
+    }
+}
+
+impl Iterator for TableCursor<'_> {
+    type Item = Result<Record>;
+
+    /// Step forward one row. On a fresh cursor this is the first row.
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.state {
+            State::Unpositioned => self.first(),
+            State::Done => None,
+            State::Valid => self.settle(Self::step_forward),
+        }
+    }
+}
+
+/// The rows of a rowid range, from `TableCursor::range`.
+pub struct RowidRange<'db> {
+    cursor: TableCursor<'db>,
+    /// Where the seek landed, not handed out yet.
+    first: Option<Option<Result<Record>>>,
+    end: Bound<i64>,
+}
+
+impl Iterator for RowidRange<'_> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = match self.first.take() {
+            Some(first) => first,
+            None => self.cursor.next(),
+        }?;
+        if let Ok(record) = &item {
+            let rowid = record.id;
+            let past_end = match self.end {
+                Bound::Included(end) => rowid > end,
+                Bound::Excluded(end) => rowid >= end,
+                Bound::Unbounded => false,
+            };
+            if past_end {
+                self.cursor.finish();
+                return None;
+            }
+        }
+        Some(item)
+    }
+}
+
+/// One index page on an `IndexCursor`'s stack, decoded. Walking it in
+/// order means child 0, entry 0, child 1, entry 1, … the right-most child;
+/// `position` counts through that sequence (a leaf only has entries).
+struct IndexFrame {
+    entries: Vec<IndexEntry>,
+    /// Empty on a leaf, one more than `entries` on an interior page.
+    children: Vec<usize>,
+    position: usize,
+}
+
+/// Streaming walk over the entries of an index B-tree whose leading key
+/// columns equal `prefix`, yielding their rowids in index order. An empty
+/// prefix walks the whole index. Pages are read as the walk reaches them,
+/// so stopping early leaves the rest of the index unread.
+pub(crate) struct IndexCursor<'db> {
+    db: &'db Database,
+    root_page: usize,
+    prefix: Vec<RecordValue>,
+    stack: Vec<IndexFrame>,
+    state: State,
+}
+
+impl<'db> IndexCursor<'db> {
+    pub(crate) fn new(db: &'db Database, root_page: usize, prefix: Vec<RecordValue>) -> Self {
+        Self {
+            db,
+            root_page,
+            prefix,
+            stack: Vec::new(),
+            state: State::Unpositioned,
+        }
+    }
+
+    fn cmp_prefix(&self, entry: &IndexEntry) -> Ordering {
+        entry.cmp_prefix(&self.prefix, self.db.header().text_encoding)
+    }
+
+    /// Read one page of the index, positioned at its start.
+    fn load_frame(&self, page_number: usize) -> Result<IndexFrame> {
+        let page = self.db.load_page(page_number)?;
+        match page.typ {
+            PageType::IndexLeaf => Ok(IndexFrame {
+                entries: page.index_leaf_entries(self.db)?,
+                children: Vec::new(),
+                position: 0,
+            }),
+            PageType::IndexInterior => {
+                let (entries, mut children): (Vec<_>, Vec<_>) = page
+                    .index_interior_entries(self.db)?
+                    .into_iter()
+                    .map(|(entry, child)| (entry, child as usize))
+                    .unzip();
+                children.extend(page.right_most_child.map(|child| child as usize));
+                Ok(IndexFrame {
+                    entries,
+                    children,
+                    position: 0,
+                })
+            }
+            _ => Err(page.wrong_type("index")),
+        }
+    }
+
+    /// Push the path to the first entry that is not below the prefix.
+    /// Equal entries can sit in the child left of an equal interior entry,
+    /// so each level descends left of the first one that isn't smaller.
+    fn seek(&mut self) -> Result<()> {
+        let mut page_number = self.root_page;
+        loop {
+            let mut frame = self.load_frame(page_number)?;
+            let first = frame
+                .entries
+                .iter()
+                .position(|entry| self.cmp_prefix(entry).is_ge())
+                .unwrap_or(frame.entries.len());
+            if frame.children.is_empty() {
+                frame.position = first;
+                self.stack.push(frame);
+                return Ok(());
+            }
+            // The child is walked now, its entry comes next.
+            frame.position = 2 * first + 1;
+            page_number = frame.children[first];
+            self.stack.push(frame);
+        }
+    }
+
+    /// The next entry in index order, from wherever the stack is.
+    fn step(&mut self) -> Result<Option<IndexEntry>> {
+        loop {
+            let Some(frame) = self.stack.last_mut() else {
+                return Ok(None);
+            };
+            let position = frame.position;
+            frame.position += 1;
+            if frame.children.is_empty() {
+                match frame.entries.get(position) {
+                    Some(entry) => return Ok(Some(entry.clone())),
+                    None => {
+                        self.stack.pop();
+                    }
+                }
+            } else if position % 2 == 1 {
+                match frame.entries.get(position / 2) {
+                    Some(entry) => return Ok(Some(entry.clone())),
+                    None => {
+                        self.stack.pop();
+                    }
+                }
+            } else {
+                let child = frame.children[position / 2];
+                self.descend_leftmost(child)?;
+            }
+        }
+    }
+
+    /// Push the path to the left-most entry under `page_number`.
+    fn descend_leftmost(&mut self, mut page_number: usize) -> Result<()> {
+        loop {
+            let mut frame = self.load_frame(page_number)?;
+            if frame.children.is_empty() {
+                self.stack.push(frame);
+                return Ok(());
+            }
+            frame.position = 1;
+            page_number = frame.children[0];
+            self.stack.push(frame);
+        }
+    }
+
+    fn next_rowid(&mut self) -> Result<Option<i64>> {
+        if self.state == State::Unpositioned {
+            self.state = State::Valid;
+            self.seek()?;
+        }
+        match self.step()? {
+            Some(entry) if self.cmp_prefix(&entry).is_eq() => Ok(Some(entry.rowid)),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Iterator for IndexCursor<'_> {
+    type Item = Result<i64>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state == State::Done {
+            return None;
+        }
+        let rowid = self.next_rowid().transpose();
+        if !matches!(rowid, Some(Ok(_))) {
+            self.stack.clear();
+            self.state = State::Done;
+        }
+        rowid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Table `t` of deep.db: rowids 2, 4, … 8000, three levels deep.
+    const LAST: i64 = 8000;
+
+    fn deep_db() -> Database {
+        Database::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/deep.db"
+        ))
+        .unwrap()
+    }
+
+    fn cursor(db: &Database) -> TableCursor<'_> {
+        db.table_cursor(db.schema().unwrap().table("t").unwrap().root_page)
+    }
+
+    fn id(item: Option<Result<Record>>) -> Option<i64> {
+        item.map(|record| record.unwrap().id)
+    }
+
+    #[test]
+    fn seek_finds_the_rowid_or_the_next_one_up() {
+        let db = deep_db();
+        let mut cursor = cursor(&db);
+        assert_eq!(id(cursor.seek(1000)), Some(1000));
+        assert_eq!(cursor.stack.len(), 3);
+        assert_eq!(id(cursor.next()), Some(1002));
+
+        // Every gap, so every leaf and interior boundary gets crossed.
+        for rowid in (1..LAST).step_by(2) {
+            assert_eq!(id(cursor.seek(rowid)), Some(rowid + 1));
+        }
+        assert_eq!(id(cursor.seek(i64::MIN)), Some(2));
+        assert_eq!(id(cursor.seek(-7)), Some(2));
+
+        assert_eq!(id(cursor.seek(LAST)), Some(LAST));
+        assert!(cursor.next().is_none());
+        assert_eq!(id(cursor.seek(LAST + 1)), None);
+        assert!(cursor.next().is_none());
+        assert!(cursor.prev().is_none());
+    }
+
+    #[test]
+    fn walks_forwards_and_backwards_through_every_row() {
+        let db = deep_db();
+        let expected: Vec<i64> = (1..=LAST / 2).map(|i| 2 * i).collect();
+        let forwards: Vec<i64> = cursor(&db).map(|record| record.unwrap().id).collect();
+        assert_eq!(forwards, expected);
+
+        let mut cursor = cursor(&db);
+        let mut backwards = vec![id(cursor.seek_last()).unwrap()];
+        while let Some(rowid) = id(cursor.prev()) {
+            backwards.push(rowid);
+        }
+        backwards.reverse();
+        assert_eq!(backwards, expected);
+        assert!(cursor.next().is_none());
+
+        // A fresh cursor's prev starts from the end.
+        assert_eq!(id(self::cursor(&db).prev()), Some(LAST));
+    }
+
+    #[test]
+    fn prev_and_next_cross_leaf_boundaries() {
+        let db = deep_db();
+        let mut cursor = cursor(&db);
+        let mut rowid = id(cursor.first()).unwrap();
+        let mut boundaries = 0;
+        loop {
+            let first_on_leaf = cursor.stack.last().is_some_and(|(_, index)| *index == 0);
+            if first_on_leaf && rowid > 2 {
+                boundaries += 1;
+                assert_eq!(id(cursor.prev()), Some(rowid - 2));
+                assert_eq!(id(cursor.next()), Some(rowid));
+            }
+            match id(cursor.next()) {
+                Some(next) => {
+                    assert_eq!(next, rowid + 2);
+                    rowid = next;
+                }
+                None => break,
+            }
+        }
+        assert_eq!(rowid, LAST);
+        // More leaves than one interior page can point at.
+        assert!(boundaries > 100, "only {} leaf boundaries", boundaries);
+    }
+
+    /// The rowids of the rows `keep` picks, in the order of their `v`.
+    fn by_v(keep: impl Fn(i64) -> bool) -> Vec<i64> {
+        let mut rows: Vec<i64> = (1..=LAST / 2).filter(|&i| keep(i)).collect();
+        rows.sort_by_key(|i| format!("row {}", i));
+        rows.into_iter().map(|i| 2 * i).collect()
+    }
+
+    fn index_rowids(db: &Database, name: &str, prefix: Vec<RecordValue>) -> Vec<i64> {
+        let schema = db.schema().unwrap();
+        let index = schema.indexes().iter().find(|index| index.name == name);
+        db.index_cursor(index.unwrap().root_page, prefix)
+            .map(|rowid| rowid.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn index_cursor_walks_interior_entries_in_order() {
+        let db = deep_db();
+        assert_eq!(index_rowids(&db, "t_v", Vec::new()), by_v(|_| true));
+        assert_eq!(
+            index_rowids(&db, "t_gv", vec![RecordValue::Int(3)]),
+            by_v(|i| i % 7 == 3)
+        );
+        let row = RecordValue::Text("row 1234".to_string());
+        assert_eq!(index_rowids(&db, "t_v", vec![row]), [2468]);
+        let between = RecordValue::Text("row 1234x".to_string());
+        assert!(index_rowids(&db, "t_v", vec![between]).is_empty());
+        assert!(index_rowids(&db, "t_gv", vec![RecordValue::Int(7)]).is_empty());
+    }
+}
@@ -13,31 +13,143 @@
 //! 1. `Page` – low-level decoding helpers.
 //! 2. `Database` – high-level walkers that collect rows.
 //!
+//! Row-at-a-time walking of table B-trees lives in `sqlite::cursor`; bad
+//! bytes come back as `sqlite::Error` (see `sqlite::error`), never a panic.
+//!
 use std::{
+    borrow::Cow,
+    cell::{OnceCell, RefCell},
+    cmp::Ordering,
+    fmt,
     fs::File,
     io::{Read, Seek, SeekFrom},
+    ops::ControlFlow,
+    rc::Rc,
 };
 
-const DB_HEADER_SIZE: usize = 100;
+use super::cache::{CacheStats, PageCache};
+use super::cursor::{IndexCursor, TableCursor};
+use super::error::{Error, Result};
+use super::eval::format_real;
+use super::header::{DatabaseHeader, TextEncoding, HEADER_SIZE};
+use super::schema::Schema;
+use super::varint;
+use super::wal::Wal;
 
-#[derive(Debug, Clone)]
+/// Pages kept in memory by default (~8 MB with 4 KB pages).
+pub const DEFAULT_CACHE_PAGES: usize = 2000;
+
+/// A single value, one variant per SQLite storage class.
+#[derive(Debug, Clone, PartialEq)]
 pub enum RecordValue {
     Null,
     Int(i64),
     Real(f64),
     Text(String),
-    #[allow(dead_code)]
     Blob(Vec<u8>),
 }
 
-#[derive(Debug)]
+impl fmt::Display for RecordValue {
+    /// The value as text, the way SQLite prints it: reals with up to 15
+    /// significant digits, blobs as their (lossily decoded) bytes.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordValue::Null => f.write_str("NULL"),
+            RecordValue::Int(n) => write!(f, "{}", n),
+            RecordValue::Real(x) => f.write_str(&format_real(*x)),
+            RecordValue::Text(text) => f.write_str(text),
+            RecordValue::Blob(bytes) => f.write_str(&String::from_utf8_lossy(bytes)),
+        }
+    }
+}
+
+impl RecordValue {
+    /// Compare two values the way SQLite orders them in an index:
+    /// NULL < INTEGER/REAL (compared numerically) < TEXT < BLOB.
+    /// Text and blobs compare byte-wise (the BINARY collation), text as
+    /// UTF-8.
+    pub fn sqlite_cmp(&self, other: &RecordValue) -> Ordering {
+        self.sqlite_cmp_in(other, TextEncoding::Utf8)
+    }
+
+    /// Like `sqlite_cmp`, but text compares byte-wise in `encoding`, the
+    /// way it does in a database stored in that encoding.
+    pub fn sqlite_cmp_in(&self, other: &RecordValue, encoding: TextEncoding) -> Ordering {
+        use RecordValue::*;
+
+        fn type_rank(value: &RecordValue) -> u8 {
+            match value {
+                Null => 0,
+                Int(_) | Real(_) => 1,
+                Text(_) => 2,
+                Blob(_) => 3,
+            }
+        }
+
+        match (self, other) {
+            (Int(a), Int(b)) => a.cmp(b),
+            (Real(a), Real(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (Int(a), Real(b)) => compare_int_real(*a, *b),
+            (Real(a), Int(b)) => compare_int_real(*b, *a).reverse(),
+            (Text(a), Text(b)) => encoding.compare(a, b),
+            (Blob(a), Blob(b)) => a.cmp(b),
+            _ => type_rank(self).cmp(&type_rank(other)),
+        }
+    }
+}
+
+/// Integer vs real without losing precision on big integers.
+fn compare_int_real(int: i64, real: f64) -> Ordering {
+    if real.is_nan() {
+        return Ordering::Greater;
+    }
+    // Outside i64 range the real wins (or loses) outright.
+    if real >= 9_223_372_036_854_775_808.0 {
+        return Ordering::Less;
+    }
+    if real < -9_223_372_036_854_775_808.0 {
+        return Ordering::Greater;
+    }
+    let truncated = real.trunc();
+    match int.cmp(&(truncated as i64)) {
+        Ordering::Equal => 0.0
+            .partial_cmp(&(real - truncated))
+            .unwrap_or(Ordering::Equal),
+        other => other,
+    }
+}
+
+/// One row of a table as stored: its rowid and its column values. An
+/// INTEGER PRIMARY KEY column is stored as NULL, its value is the rowid.
+#[derive(Debug, Clone)]
 pub struct Record {
-    #[allow(dead_code)]
-    pub id: u64,
+    pub id: i64,
     pub values: Vec<RecordValue>,
 }
 
-#[derive(Debug)]
+/// One entry of an index B-tree: the indexed column values plus the rowid
+/// of the table row they belong to.
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    pub key: Vec<RecordValue>,
+    pub rowid: i64,
+}
+
+impl IndexEntry {
+    /// Compare only the first `prefix.len()` key columns against `prefix`.
+    /// Text compares in the order of the database's `encoding`, which is
+    /// the order the index was built in.
+    pub fn cmp_prefix(&self, prefix: &[RecordValue], encoding: TextEncoding) -> Ordering {
+        self.key
+            .iter()
+            .zip(prefix)
+            .map(|(column, wanted)| column.sqlite_cmp_in(wanted, encoding))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PageType {
     TableLeaf,
     TableInterior,
@@ -47,23 +159,75 @@ pub enum PageType {
 
 #[derive(Debug)]
 pub struct Page {
-    #[allow(dead_code)]
+    /// Page number in the file, starting at 1. Only used to say where
+    /// things went wrong.
+    number: usize,
     pub typ: PageType,
     pub cell_pointers: Vec<usize>,
     pub right_most_child: Option<u32>,
-    data: Vec<u8>,
+    /// The page as read from the file (page 1 without the database
+    /// header), shared with the page cache.
+    raw: Rc<[u8]>,
+    /// Where the cell pointer array ends in `raw`; `data()` starts here and
+    /// cell pointers are indexes into it.
+    data_offset: usize,
+    /// Offsets of the page header (100 on page 1, 0 elsewhere) and of
+    /// `data()[0]` from the start of the page, so errors can point at bytes
+    /// the way the file format spec counts them.
+    header_start: usize,
+    content_start: usize,
+    /// Page size minus the reserved bytes at the end of every page.
+    usable_size: usize,
+    /// How TEXT values in this page's records are encoded.
+    encoding: TextEncoding,
+}
+
+/// Where a page gets the overflow pages its cells spill onto, when a
+/// payload is decoded: `Database` reads them through its page cache.
+pub(crate) trait PageSource {
+    /// Raw bytes of page `page_number`.
+    fn page_data(&self, page_number: usize) -> Result<Rc<[u8]>>;
+}
+
+/// Why a record failed to decode. The page turns this into an `Error`
+/// that says where the record sits.
+#[derive(Debug, PartialEq)]
+enum RecordError {
+    HeaderSize(usize),
+    Truncated,
+    InvalidSerialType(u64),
 }
 
 impl Page {
-    fn from_data(page_size: u16, data: Vec<u8>) -> Self {
-        let typ = match data[0] {
-            13 => PageType::TableLeaf,
-            5 => PageType::TableInterior,
-            10 => PageType::IndexLeaf,
-            2 => PageType::IndexInterior,
-            _ => panic!("Invalid page type: {}", data[0]),
+    fn from_data(
+        number: usize,
+        page_size: u32,
+        usable_size: usize,
+        encoding: TextEncoding,
+        data: Rc<[u8]>,
+    ) -> Result<Self> {
+        // Page 1 arrives without the 100-byte database header.
+        let header_start = page_size as usize - data.len();
+        let corrupt =
+            |offset: usize, reason: String| Error::corrupt(number, header_start + offset, reason);
+
+        let typ = match data.first() {
+            Some(13) => PageType::TableLeaf,
+            Some(5) => PageType::TableInterior,
+            Some(10) => PageType::IndexLeaf,
+            Some(2) => PageType::IndexInterior,
+            Some(other) => return Err(corrupt(0, format!("unknown page type {}", other))),
+            None => return Err(corrupt(0, "page is empty".to_string())),
         };
 
+        let cell_pointer_start = match typ {
+            PageType::TableInterior | PageType::IndexInterior => 12, // Interior pages start at 12
+            PageType::TableLeaf | PageType::IndexLeaf => 8,          // Leaf pages start at 8
+        };
+        if data.len() < cell_pointer_start {
+            return Err(corrupt(0, "page header is truncated".to_string()));
+        }
+
         let right_most_child = match typ {
             PageType::TableInterior | PageType::IndexInterior => {
                 // Bytes 8-11 contain the rightmost child page number for interior pages
@@ -74,55 +238,165 @@ impl Page {
         };
 
         let cell_count = u16::from_be_bytes([data[3], data[4]]);
-        let data_offset = match typ {
-            PageType::TableInterior | PageType::IndexInterior => {
-                12 + (cell_count as usize * 2) // Interior pages: 12-byte header
-            }
-            PageType::TableLeaf | PageType::IndexLeaf => {
-                8 + (cell_count as usize * 2) // Leaf pages: 8-byte header
+        let data_offset = cell_pointer_start + cell_count as usize * 2;
+        if data_offset > data.len() {
+            return Err(corrupt(
+                3,
+                format!("{} cell pointers do not fit on the page", cell_count),
+            ));
+        }
+        let content_start = header_start + data_offset;
+
+        let mut cell_pointers = Vec::with_capacity(cell_count as usize);
+        for (i, chunk) in data[cell_pointer_start..data_offset].chunks(2).enumerate() {
+            let pointer = u16::from_be_bytes([chunk[0], chunk[1]]) as usize;
+            if pointer < content_start || pointer >= usable_size {
+                return Err(corrupt(
+                    cell_pointer_start + i * 2,
+                    format!("cell pointer {} is outside the cell content area", pointer),
+                ));
             }
-        };
-        let offset = (page_size as usize) - data.len() + data_offset;
+            cell_pointers.push(pointer - content_start);
+        }
 
-        let cell_pointer_start = match typ {
-            PageType::TableInterior | PageType::IndexInterior => 12, // Interior pages start at 12
-            PageType::TableLeaf | PageType::IndexLeaf => 8,          // Leaf pages start at 8
+        Ok(Self {
+            number,
+            typ,
+            cell_pointers,
+            right_most_child,
+            raw: data,
+            data_offset,
+            header_start,
+            content_start,
+            usable_size,
+            encoding,
+        })
+    }
+
+    /// The cell content area: everything after the cell pointer array.
+    fn data(&self) -> &[u8] {
+        &self.raw[self.data_offset..]
+    }
+
+    /// An error about the bytes at `pointer` (an index into `data`).
+    pub(crate) fn corrupt(&self, pointer: usize, reason: impl Into<String>) -> Error {
+        Error::corrupt(self.number, self.content_start + pointer, reason)
+    }
+
+    /// A B-tree pointed us at the wrong kind of page.
+    pub(crate) fn wrong_type(&self, expected: &str) -> Error {
+        Error::corrupt(
+            self.number,
+            self.header_start,
+            format!("expected {} page, found {:?}", expected, self.typ),
+        )
+    }
+
+    // ------------------------------------------------------------------
+    // Payload layout: how many bytes of a cell's payload stay on this
+    // page, and where the rest went.
+    // ------------------------------------------------------------------
+    // From the file format spec (U = usable size, P = payload size):
+    // • X = U-35 for table leaves, ((U-12)*64/255)-23 for index pages.
+    // • M = ((U-12)*32/255)-23, K = M+((P-M)%(U-4)).
+    // • P <= X → everything is local; else K if K <= X, else M.
+    fn local_payload_size(&self, payload_size: usize) -> usize {
+        let usable = self.usable_size;
+        let max_local = match self.typ {
+            PageType::TableLeaf => usable - 35,
+            _ => ((usable - 12) * 64 / 255) - 23,
         };
+        if payload_size <= max_local {
+            return payload_size;
+        }
+        let min_local = ((usable - 12) * 32 / 255) - 23;
+        let k = min_local + ((payload_size - min_local) % (usable - 4));
+        if k <= max_local {
+            k
+        } else {
+            min_local
+        }
+    }
 
-        let cell_pointers = data[cell_pointer_start..data_offset]
-            .chunks(2)
-            .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]) as usize - offset)
-            .collect();
+    /// Returns `(payload_start, payload_size)` for the cell at `pointer`.
+    /// Table interior cells carry no payload and yield `None`.
+    fn cell_payload_bounds(&self, pointer: usize) -> Option<(usize, usize)> {
+        let mut offset = match self.typ {
+            PageType::TableLeaf | PageType::IndexLeaf => pointer,
+            PageType::IndexInterior => pointer + 4, // skip left child pointer
+            PageType::TableInterior => return None,
+        };
+        let payload_size = Self::get_varint(self.data(), &mut offset) as usize;
+        if matches!(self.typ, PageType::TableLeaf) {
+            Self::get_varint(self.data(), &mut offset); // rowid
+        }
+        Some((offset, payload_size))
+    }
 
-        let data = data[data_offset..].to_vec();
+    /// The complete payload of a cell: straight from the page when it fits,
+    /// otherwise reassembled from its overflow chain, read only now that
+    /// the cell is being decoded.
+    ///
+    /// ```text
+    ///  cell: [local bytes][next=7] → page 7: [next=9][bytes] → page 9: [next=0][bytes]
+    /// ```
+    fn payload(&self, pointer: usize, pages: &dyn PageSource) -> Result<Cow<'_, [u8]>> {
+        let Some((start, payload_size)) = self.cell_payload_bounds(pointer) else {
+            return Ok(Cow::Borrowed(&[]));
+        };
+        let local = self.local_payload_size(payload_size);
+        if local == payload_size {
+            return self
+                .data()
+                .get(start..start + payload_size)
+                .map(Cow::Borrowed)
+                .ok_or_else(|| self.corrupt(pointer, "cell payload runs off the page"));
+        }
 
-        Self {
-            typ,
-            cell_pointers,
-            right_most_child,
-            data,
+        let at = start + local;
+        let Some(next) = self.data().get(at..at + 4) else {
+            return Err(self.corrupt(pointer, "overflow page number runs off the page"));
+        };
+        let mut next_page = u32::from_be_bytes([next[0], next[1], next[2], next[3]]);
+        let mut payload = Vec::with_capacity(payload_size);
+        payload.extend_from_slice(&self.data()[start..at]);
+        while payload.len() < payload_size {
+            if next_page == 0 {
+                return Err(self.corrupt(
+                    pointer,
+                    format!(
+                        "overflow chain ended early: got {} of {} payload bytes",
+                        payload.len(),
+                        payload_size
+                    ),
+                ));
+            }
+            let data = pages.page_data(next_page as usize)?;
+            // Overflow page: [4-byte next page][content up to usable size]
+            let content_end = (4 + payload_size - payload.len()).min(self.usable_size);
+            payload.extend_from_slice(&data[4..content_end]);
+            next_page = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
         }
+        Ok(Cow::Owned(payload))
     }
 
     // ------------------------------------------------------------
-    // Helper: read a SQLite *varint* (1-9 byte variable-length int)
+    // Helper: read a SQLite *varint* (1-9 byte variable-length int, see
+    //          `sqlite::varint`) at `offset` and step past it.
     // ------------------------------------------------------------
-    // Rules recap in kid-speak:
-    // • Only the lowest 7 bits of each byte belong to the number.
-    // • The highest bit (0x80) tells us if more bytes follow (1 = yes).
-    // • We keep shifting our previous bits left by 7 and add the new 7.
+    // A varint cut off by the end of `data` reads as 0 and leaves `offset`
+    // at the end, where the caller's bounds checks catch it.
     fn get_varint(data: &[u8], offset: &mut usize) -> u64 {
-        let mut value = 0u64;
-        for (i, byte) in data[*offset..].iter().enumerate() {
-            value <<= 7;
-            value += (byte & 0x7F) as u64;
-            if byte & 0x80 == 0 {
-                *offset += i + 1;
-                return value;
+        match data.get(*offset..).and_then(varint::read) {
+            Some((value, len)) => {
+                *offset += len;
+                value
+            }
+            None => {
+                *offset = data.len();
+                0
             }
         }
-        *offset += data.len();
-        value
     }
 
     // -------------------------------------------------------------------
@@ -143,28 +417,43 @@ impl Page {
         Ok(bytes)
     }
 
-    /// Parse a SQLite record (starting at the *header size* varint) and return
-    /// (values, bytes_consumed).
-    /// This helper is shared by table and index cell parsing.
-    fn parse_record_values(data: &[u8]) -> (Vec<RecordValue>, usize) {
-        let mut local_offset = 0;
+    // Integers are stored big-endian two's complement in 1, 2, 3, 4, 6 or 8
+    // bytes. After right-aligning, shift the top byte's sign bit up to bit 63
+    // and back down again so it fills the empty high bytes:
+    //
+    //   0xFF (1 byte)  →  00 .. 00 FF  →  FF 00 .. 00  →  FF .. FF FF = -1
+    fn sign_extend(bytes: [u8; 8], n: usize) -> i64 {
+        let unused_bits = 64 - 8 * n as u32;
+        (i64::from_be_bytes(bytes) << unused_bits) >> unused_bits
+    }
 
-        // 1. header size varint
-        let header_size = Self::get_varint(data, &mut local_offset) as usize;
+    /// Parse a SQLite record, starting at the *header size* varint.
+    /// This helper is shared by table and index cell parsing.
+    fn parse_record_values(
+        data: &[u8],
+        encoding: TextEncoding,
+    ) -> Result<Vec<RecordValue>, RecordError> {
+        let mut header_offset = 0;
 
-        // 2. The header area follows immediately after the varint we just read.
-        let header_start = local_offset;
-        let header_end = header_start + header_size - 1; // -1 because header size includes itself
+        // 1. header size varint; the size counts the varint itself
+        let header_size = Self::get_varint(data, &mut header_offset) as usize;
+        if header_size < header_offset || header_size > data.len() {
+            return Err(RecordError::HeaderSize(header_size));
+        }
 
-        // 3. Values segment starts right after the header area
-        let mut values_iter = data[header_end..].iter().copied();
+        // 2. The serial types fill the rest of the header, and the values
+        //    segment starts right after it.
+        let header = &data[..header_size];
+        let mut values_iter = data[header_size..].iter().copied();
 
         let mut values = Vec::new();
-        let mut header_offset = 0;
 
         // Iterate over serial types in header area
-        while header_offset < header_size - 1 {
-            let serial_type = Self::get_varint(&data[header_start..header_end], &mut header_offset);
+        while header_offset < header_size {
+            let Some((serial_type, len)) = varint::read(&header[header_offset..]) else {
+                return Err(RecordError::Truncated);
+            };
+            header_offset += len;
 
             let value = match serial_type {
                 0 => RecordValue::Null,
@@ -174,233 +463,361 @@ impl Page {
                         6 => 8,
                         v => v,
                     } as usize;
-                    match Self::get_be_bytes(n, &mut values_iter) {
-                        Ok(bytes) => RecordValue::Int(i64::from_be_bytes(bytes)),
-                        Err(_) => return (values, header_end + header_offset),
-                    }
+                    let bytes = Self::get_be_bytes(n, &mut values_iter)
+                        .map_err(|_| RecordError::Truncated)?;
+                    RecordValue::Int(Self::sign_extend(bytes, n))
+                }
+                7 => {
+                    let bytes = Self::get_be_bytes(8, &mut values_iter)
+                        .map_err(|_| RecordError::Truncated)?;
+                    RecordValue::Real(f64::from_be_bytes(bytes))
                 }
-                7 => match Self::get_be_bytes(8, &mut values_iter) {
-                    Ok(bytes) => RecordValue::Real(f64::from_be_bytes(bytes)),
-                    Err(_) => return (values, header_end + header_offset),
-                },
                 8 => RecordValue::Int(0),
                 9 => RecordValue::Int(1),
                 serial if serial >= 12 => {
                     let length = ((serial - 12) / 2) as usize;
                     let bytes: Vec<u8> = (&mut values_iter).take(length).collect();
+                    if bytes.len() < length {
+                        return Err(RecordError::Truncated);
+                    }
                     if serial % 2 == 0 {
                         RecordValue::Blob(bytes)
                     } else {
-                        RecordValue::Text(String::from_utf8_lossy(&bytes).to_string())
+                        RecordValue::Text(encoding.decode(&bytes))
                     }
                 }
-                _ => panic!("Invalid serial type: {}", serial_type),
+                // 10 and 11 are reserved for internal use and never appear
+                // in a well-formed database file.
+                serial => return Err(RecordError::InvalidSerialType(serial)),
             };
 
             values.push(value);
         }
 
-        // Exact byte count isn't vital for current callers; we return header_end
-        // (bytes up to the start of the values segment) as an approximation.
-        (values, header_end)
+        Ok(values)
     }
 
-    fn get_record(&self, pointer: usize) -> Record {
-        let mut offset = pointer;
-        let size = Self::get_varint(&self.data, &mut offset) as usize;
-        let id = Self::get_varint(&self.data, &mut offset) as u64;
-
-        // Delegate to common parser for record values
-        let (values, _consumed) = Self::parse_record_values(&self.data[offset..]);
-
-        Record { id, values }
+    /// Decode the record stored in the cell at `pointer`.
+    fn record_values(&self, pointer: usize, pages: &dyn PageSource) -> Result<Vec<RecordValue>> {
+        let payload = self.payload(pointer, pages)?;
+        Self::parse_record_values(&payload, self.encoding).map_err(|err| match err {
+            RecordError::HeaderSize(size) => self.corrupt(
+                pointer,
+                format!("record header size {} is out of range", size),
+            ),
+            RecordError::Truncated => {
+                self.corrupt(pointer, "record is shorter than its header says")
+            }
+            RecordError::InvalidSerialType(serial_type) => Error::InvalidSerialType {
+                page: self.number,
+                offset: self.content_start + pointer,
+                serial_type,
+            },
+        })
     }
 
-    pub fn records(&self) -> impl Iterator<Item = Record> + '_ {
-        self.cell_pointers.iter().map(|i| self.get_record(*i))
+    fn get_record(&self, pointer: usize, pages: &dyn PageSource) -> Result<Record> {
+        let mut offset = pointer;
+        let _payload_size = Self::get_varint(self.data(), &mut offset) as usize;
+        // Rowids are stored as the varint of their two's complement bits.
+        let id = Self::get_varint(self.data(), &mut offset) as i64;
+
+        Ok(Record {
+            id,
+            values: self.record_values(pointer, pages)?,
+        })
     }
 
     pub fn is_leaf(&self) -> bool {
         matches!(self.typ, PageType::TableLeaf | PageType::IndexLeaf)
     }
 
-    pub fn get_child_pages(&self) -> Vec<u32> {
-        match self.typ {
-            PageType::TableLeaf => Vec::new(), // Leaf pages have no children
-            PageType::TableInterior | PageType::IndexInterior => {
-                let mut child_pages = Vec::new();
-
-                // Each cell in an interior page contains a child page number
-                for &pointer in &self.cell_pointers {
-                    // Interior page cell format: [4-byte child page][varint key]
-                    if pointer + 4 <= self.data.len() {
-                        let child_page = u32::from_be_bytes([
-                            self.data[pointer],
-                            self.data[pointer + 1],
-                            self.data[pointer + 2],
-                            self.data[pointer + 3],
-                        ]);
-                        child_pages.push(child_page);
-                    }
-                }
-
-                // Don't forget the rightmost child!
-                if let Some(rightmost) = self.right_most_child {
-                    child_pages.push(rightmost);
-                }
-
-                child_pages
-            }
-            _ => Vec::new(), // Leaf pages have no children (TableLeaf & IndexLeaf)
-        }
-    }
-
     // ---------------- Index-specific helpers ----------------
 
-    /// Parse a cell in an **index leaf** page (page type 0x0A) and return `(country, rowid)`.
-    fn get_index_leaf_entry(&self, pointer: usize) -> (String, u64) {
-        let mut offset = pointer;
-
-        // First varint: payload size (we can ignore the exact value)
-        let _payload_size = Self::get_varint(&self.data, &mut offset) as usize;
-
-        // Next bytes start the record (header size varint comes first).
-        let (values, _) = Self::parse_record_values(&self.data[offset..]);
-
-        if values.len() != 2 {
-            panic!(
-                "Index leaf record expected 2 columns (country, rowid), got {}",
-                values.len()
-            );
-        }
-
-        let country = match &values[0] {
-            RecordValue::Text(s) => s.clone(),
-            _ => panic!("Expected TEXT in first column of index record"),
+    /// Decode an index record: every column but the last is the key, the
+    /// last one is the rowid of the table row it points at.
+    fn index_entry(&self, pointer: usize, pages: &dyn PageSource) -> Result<IndexEntry> {
+        let mut key = self.record_values(pointer, pages)?;
+
+        let rowid = match key.pop() {
+            Some(RecordValue::Int(n)) => n,
+            other => {
+                return Err(self.corrupt(
+                    pointer,
+                    format!(
+                        "expected an integer rowid at the end of an index record, got {:?}",
+                        other
+                    ),
+                ))
+            }
         };
+        if key.is_empty() {
+            return Err(self.corrupt(pointer, "index record has no key columns"));
+        }
 
-        let rowid = match &values[1] {
-            RecordValue::Int(n) => *n as u64,
-            _ => panic!("Expected INT rowid in second column of index record"),
-        };
+        Ok(IndexEntry { key, rowid })
+    }
 
-        (country, rowid)
+    /// Parse a cell in an **index leaf** page (page type 0x0A).
+    fn get_index_leaf_entry(&self, pointer: usize, pages: &dyn PageSource) -> Result<IndexEntry> {
+        // The payload is a plain record (header size varint comes first).
+        self.index_entry(pointer, pages)
     }
 
     /// Parse a cell in an **index interior** page (page type 0x02).
-    /// Returns `(country_key, child_page)`
-    fn get_index_interior_entry(&self, pointer: usize) -> (String, u32) {
-        // After the 4-byte child pointer comes a varint for payload size
-        let mut offset = pointer + 4;
-        let _payload_size = Self::get_varint(&self.data, &mut offset);
-
-        // Now `offset` points at the start of the record header-size varint
-        let (values, _) = Self::parse_record_values(&self.data[offset..]);
-
-        if values.is_empty() {
-            panic!("Index interior record expected at least 1 column (country key)");
-        }
-
-        let country = match &values[0] {
-            RecordValue::Text(s) => s.clone(),
-            RecordValue::Int(n) => n.to_string(),
-            RecordValue::Real(f) => f.to_string(),
-            RecordValue::Null => "NULL".to_string(),
-            RecordValue::Blob(_) => "[BLOB]".to_string(),
-        };
-
-        let child_page = u32::from_be_bytes([
-            self.data[pointer],
-            self.data[pointer + 1],
-            self.data[pointer + 2],
-            self.data[pointer + 3],
-        ]);
-
-        (country, child_page)
+    /// Returns `(entry, child_page)`: unlike table B-trees, interior index
+    /// cells are real entries, not just separators.
+    fn get_index_interior_entry(
+        &self,
+        pointer: usize,
+        pages: &dyn PageSource,
+    ) -> Result<(IndexEntry, u32)> {
+        // After the 4-byte child pointer and payload size comes the record
+        let entry = self.index_entry(pointer, pages)?;
+        Ok((entry, self.child_pointer(pointer)?))
     }
 
     /// Convenience iterator over index leaf entries (only valid for IndexLeaf pages).
-    pub fn index_leaf_entries(&self) -> Vec<(String, u64)> {
-        if !matches!(self.typ, PageType::IndexLeaf) {
-            panic!("Called index_leaf_entries on non-index-leaf page");
-        }
+    pub(crate) fn index_leaf_entries(&self, pages: &dyn PageSource) -> Result<Vec<IndexEntry>> {
+        self.expect_type(PageType::IndexLeaf)?;
         self.cell_pointers
             .iter()
-            .map(|&ptr| self.get_index_leaf_entry(ptr))
+            .map(|&ptr| self.get_index_leaf_entry(ptr, pages))
             .collect()
     }
 
-    /// Returns vector of `(country_key, child_page)` for index interior page.
-    pub fn index_interior_entries(&self) -> Vec<(String, u32)> {
-        if !matches!(self.typ, PageType::IndexInterior) {
-            panic!("Called index_interior_entries on non-index-interior page");
-        }
+    /// Returns vector of `(entry, child_page)` for index interior page.
+    pub(crate) fn index_interior_entries(
+        &self,
+        pages: &dyn PageSource,
+    ) -> Result<Vec<(IndexEntry, u32)>> {
+        self.expect_type(PageType::IndexInterior)?;
         self.cell_pointers
             .iter()
-            .map(|&ptr| self.get_index_interior_entry(ptr))
+            .map(|&ptr| self.get_index_interior_entry(ptr, pages))
             .collect()
     }
 
     // ---------------- Table interior helpers (rowid keys) ----------------
 
     /// Return (child_page, rowid_key) for a cell in a **table interior** page.
-    fn get_table_interior_entry(&self, pointer: usize) -> (u32, u64) {
-        let child_page = u32::from_be_bytes([
-            self.data[pointer],
-            self.data[pointer + 1],
-            self.data[pointer + 2],
-            self.data[pointer + 3],
-        ]);
+    fn get_table_interior_entry(&self, pointer: usize) -> Result<(u32, i64)> {
+        let child_page = self.child_pointer(pointer)?;
 
         let mut offset = pointer + 4;
-        let rowid_key = Self::get_varint(&self.data, &mut offset);
+        let rowid_key = Self::get_varint(self.data(), &mut offset) as i64;
 
-        (child_page, rowid_key)
+        Ok((child_page, rowid_key))
     }
 
-    /// Convenience to iterate interior table entries
-    pub fn table_interior_entries(&self) -> Vec<(u32, u64)> {
-        if !matches!(self.typ, PageType::TableInterior) {
-            panic!("Called table_interior_entries on non-table-interior page");
+    /// The 4-byte left child page number that starts every interior cell.
+    fn child_pointer(&self, pointer: usize) -> Result<u32> {
+        match self.data().get(pointer..pointer + 4) {
+            Some(bytes) => Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            None => Err(self.corrupt(pointer, "child page number runs off the page")),
         }
-        self.cell_pointers
-            .iter()
-            .map(|&ptr| self.get_table_interior_entry(ptr))
-            .collect()
+    }
+
+    fn expect_type(&self, expected: PageType) -> Result<()> {
+        if self.typ == expected {
+            return Ok(());
+        }
+        Err(self.wrong_type(&format!("{:?}", expected)))
+    }
+
+    // ---------------- Cell-by-index access (used by cursors) ----------------
+
+    pub(crate) fn cell_count(&self) -> usize {
+        self.cell_pointers.len()
+    }
+
+    /// Row stored in cell `index` of a **table leaf** page.
+    pub(crate) fn record_at(&self, index: usize, pages: &dyn PageSource) -> Result<Record> {
+        self.get_record(self.cell_pointers[index], pages)
+    }
+
+    /// Rowid of cell `index` of a **table leaf** page, without decoding
+    /// the rest of the row.
+    pub(crate) fn rowid_at(&self, index: usize) -> i64 {
+        let mut offset = self.cell_pointers[index];
+        Self::get_varint(self.data(), &mut offset); // payload size
+        Self::get_varint(self.data(), &mut offset) as i64
+    }
+
+    /// Child `index` of a **table interior** page; `index == cell_count()`
+    /// is the right-most child.
+    pub(crate) fn table_child(&self, index: usize) -> Result<usize> {
+        match self.cell_pointers.get(index) {
+            Some(&pointer) => match self.get_table_interior_entry(pointer)?.0 {
+                0 => Err(self.corrupt(pointer, "child page number is 0")),
+                child => Ok(child as usize),
+            },
+            None => match self.right_most_child {
+                Some(child) if child != 0 => Ok(child as usize),
+                _ => Err(Error::corrupt(
+                    self.number,
+                    self.header_start + 8,
+                    "right-most child page number is 0",
+                )),
+            },
+        }
+    }
+
+    /// Rowid key of cell `index` of a **table interior** page: every row in
+    /// that cell's child has a rowid `<=` this key.
+    pub(crate) fn table_key(&self, index: usize) -> Result<i64> {
+        Ok(self.get_table_interior_entry(self.cell_pointers[index])?.1)
     }
 }
 
+/// An open database file. Reads go through a page cache; nothing is ever
+/// written back. If a `-wal` file sits next to it, committed pages are read
+/// from there first (see `sqlite::wal`).
 #[derive(Debug)]
 pub struct Database {
-    pub page_size: u16,
-    pub root_page: Page,
+    page_size: u32,
+    /// Bytes of each page available to the B-tree (page size minus reserved).
+    usable_size: usize,
+    /// Number of pages in the database, as of the last commit.
+    page_count: usize,
+    header: DatabaseHeader,
+    /// One handle for the lifetime of the database; reads seek within it.
+    file: RefCell<File>,
+    wal: Option<Wal>,
+    cache: RefCell<PageCache>,
+    schema: OnceCell<Schema>,
+}
+
+impl PageSource for Database {
+    /// Raw bytes of a page, from the cache when possible.
+    fn page_data(&self, page_number: usize) -> Result<Rc<[u8]>> {
+        if let Some(data) = self.cache.borrow_mut().get(page_number) {
+            return Ok(data);
+        }
+        let data = self.read_page_data(page_number)?;
+        self.cache
+            .borrow_mut()
+            .insert(page_number, Rc::clone(&data));
+        Ok(data)
+    }
 }
 
 impl Database {
-    pub fn load(path: &str) -> anyhow::Result<Self> {
+    pub fn load(path: &str) -> Result<Self> {
+        Self::load_with_cache_capacity(path, DEFAULT_CACHE_PAGES)
+    }
+
+    /// Like `load`, but keep at most `capacity` pages in memory.
+    pub fn load_with_cache_capacity(path: &str, capacity: usize) -> Result<Self> {
         let mut file = File::open(path)?;
 
-        let mut db_header = [0; DB_HEADER_SIZE];
-        file.read_exact(&mut db_header)?;
-        let page_size = u16::from_be_bytes([db_header[16], db_header[17]]);
+        let mut header_bytes = [0; HEADER_SIZE];
+        file.read_exact(&mut header_bytes)
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::UnexpectedEof => {
+                    Error::InvalidHeader("file is shorter than the 100-byte header".to_string())
+                }
+                _ => Error::Io(err),
+            })?;
+        let wal = Wal::open(&format!("{}-wal", path))?;
+        // A commit that changed page 1 also changed the header on it. Until
+        // the first checkpoint, the copy in the main file may not even be
+        // complete.
+        if let Some(page) = wal
+            .as_ref()
+            .map(|wal| wal.read_page(1))
+            .transpose()?
+            .flatten()
+        {
+            header_bytes.copy_from_slice(&page[..HEADER_SIZE]);
+        }
+        let header = DatabaseHeader::parse(&header_bytes)?;
+        if let Some(wal) = &wal {
+            if wal.page_size() != header.page_size {
+                return Err(Error::InvalidHeader(format!(
+                    "the WAL has {}-byte pages but the database has {}-byte pages",
+                    wal.page_size(),
+                    header.page_size
+                )));
+            }
+        }
 
-        let mut root_page = vec![0; page_size as usize - DB_HEADER_SIZE];
-        file.read_exact(&mut root_page)?;
-        let root_page = Page::from_data(page_size, root_page);
+        let page_size = header.page_size;
+        let usable_size = header.usable_size();
+        // The last commit in the WAL knows the size best. Otherwise trust
+        // the header's page count when it is current, like SQLite, and fall
+        // back to the file size.
+        let page_count = match wal.as_ref().and_then(Wal::page_count) {
+            Some(page_count) => page_count,
+            None if header.page_count_is_valid() => header.page_count as usize,
+            None => file.metadata()?.len().div_ceil(page_size as u64) as usize,
+        };
 
-        Ok(Self {
+        let db = Self {
             page_size,
-            root_page,
-        })
+            usable_size,
+            page_count,
+            header,
+            file: RefCell::new(file),
+            wal,
+            cache: RefCell::new(PageCache::new(capacity)),
+            schema: OnceCell::new(),
+        };
+        // Decode the schema page up front so a corrupt file fails to open.
+        db.load_page(1)?;
+
+        Ok(db)
+    }
+
+    /// The decoded 100-byte database header.
+    pub fn header(&self) -> &DatabaseHeader {
+        &self.header
     }
 
-    pub fn load_page(&self, path: &str, page_number: usize) -> anyhow::Result<Page> {
-        // Validate page number
-        if page_number == 0 {
-            anyhow::bail!("Invalid page number: page numbers start from 1");
+    /// The tables, indexes, views and triggers in the file, read from
+    /// sqlite_schema the first time they're asked for.
+    pub fn schema(&self) -> Result<&Schema> {
+        if let Some(schema) = self.schema.get() {
+            return Ok(schema);
         }
+        let schema = Schema::load(self)?;
+        Ok(self.schema.get_or_init(|| schema))
+    }
 
-        let mut file = File::open(path)?;
+    /// Page size in bytes, from the database header.
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    /// Bytes of each page available to the B-tree (page size minus reserved).
+    pub fn usable_size(&self) -> usize {
+        self.usable_size
+    }
+
+    /// Number of pages in the database, as of the last commit.
+    pub fn page_count(&self) -> usize {
+        self.page_count
+    }
+
+    /// Read the raw bytes of a page. Page 1 comes back without the 100-byte
+    /// database header.
+    fn read_page_data(&self, page_number: usize) -> Result<Rc<[u8]>> {
+        // Page numbers start from 1
+        if page_number == 0 || page_number > self.page_count {
+            return Err(Error::PageOutOfRange {
+                page: page_number,
+                page_count: self.page_count,
+            });
+        }
+
+        if let Some(wal) = &self.wal {
+            if let Some(mut page_data) = wal.read_page(page_number)? {
+                if page_number == 1 {
+                    page_data.drain(..HEADER_SIZE);
+                }
+                return Ok(page_data.into());
+            }
+        }
 
         // Calculate correct page offset
         let page_offset = if page_number == 1 {
@@ -411,212 +828,379 @@ impl Database {
 
         // Calculate how much data to read
         let (read_offset, page_data_size) = if page_number == 1 {
-            (DB_HEADER_SIZE, self.page_size as usize - DB_HEADER_SIZE)
+            (HEADER_SIZE, self.page_size as usize - HEADER_SIZE)
         } else {
             (0, self.page_size as usize)
         };
 
+        let mut file = self.file.borrow_mut();
         file.seek(SeekFrom::Start((page_offset + read_offset) as u64))?;
         let mut page_data = vec![0; page_data_size];
         file.read_exact(&mut page_data)?;
 
-        Ok(Page::from_data(self.page_size, page_data))
+        Ok(page_data.into())
     }
 
-    pub fn get_all_records(
-        &self,
-        db_path: &str,
-        root_page_num: usize,
-    ) -> anyhow::Result<Vec<Record>> {
-        let mut all_records = Vec::new();
-        self.traverse_btree(db_path, root_page_num, &mut all_records)?;
-        Ok(all_records)
+    /// Decode a B-tree page. Its bytes come from the cache when possible;
+    /// overflow pages are only read once a cell on it is decoded.
+    pub(crate) fn load_page(&self, page_number: usize) -> Result<Page> {
+        Page::from_data(
+            page_number,
+            self.page_size,
+            self.usable_size,
+            self.header.text_encoding,
+            self.page_data(page_number)?,
+        )
+    }
+
+    /// Page cache hit/miss counters since the database was opened.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.borrow().stats()
     }
 
-    fn traverse_btree(
+    /// Every row of a table, in rowid order.
+    pub fn get_all_records(&self, root_page_num: usize) -> Result<Vec<Record>> {
+        self.table_cursor(root_page_num).collect()
+    }
+
+    /// Streaming cursor over a table B-tree, positioned before the first row.
+    pub fn table_cursor(&self, root_page_num: usize) -> TableCursor<'_> {
+        TableCursor::new(self, root_page_num)
+    }
+
+    /// Walk a table B-tree in rowid order, handing each row to `visit`.
+    /// Returning `ControlFlow::Break` stops the walk right there, so pages
+    /// past that point are never read (this is what makes LIMIT cheap).
+    ///
+    /// `visit` may fail with its own error type, as long as a database
+    /// `Error` converts into it.
+    pub fn scan_table<E: From<Error>>(
         &self,
-        db_path: &str,
-        page_num: usize,
-        records: &mut Vec<Record>,
-    ) -> anyhow::Result<()> {
-        let page = self.load_page(db_path, page_num)?;
-
-        if page.is_leaf() {
-            // This is a leaf page - collect all its records
-            for record in page.records() {
-                records.push(record);
+        root_page_num: usize,
+        mut visit: impl FnMut(Record) -> Result<ControlFlow<()>, E>,
+    ) -> Result<(), E> {
+        for record in self.table_cursor(root_page_num) {
+            if visit(record?)?.is_break() {
+                break;
             }
-        } else {
-            // This is an interior page - traverse all child pages
-            let child_pages = page.get_child_pages();
-            for child_page_num in child_pages {
-                // Validate child page number
-                if child_page_num == 0 {
-                    continue; // Skip invalid page numbers
+        }
+        Ok(())
+    }
+
+    /// Number of rows in a table, added up from the leaf pages' cell counts
+    /// without decoding a single record.
+    pub fn count_rows(&self, root_page_num: usize) -> Result<u64> {
+        let mut count = 0;
+        let mut pending = vec![root_page_num];
+        while let Some(page_num) = pending.pop() {
+            let page = self.load_page(page_num)?;
+            match page.typ {
+                PageType::TableLeaf => count += page.cell_count() as u64,
+                PageType::TableInterior => {
+                    for index in 0..=page.cell_count() {
+                        pending.push(page.table_child(index)?);
+                    }
                 }
-                self.traverse_btree(db_path, child_page_num as usize, records)?;
+                _ => return Err(page.wrong_type("table")),
             }
         }
-
-        Ok(())
+        Ok(count)
     }
 
     // ---------------- Index search helpers ----------------
 
-    /// Collect all rowids whose index key (country) equals `target_country`.
-    /// `index_root_page` must point to the root of an index B-tree that stores
-    /// (country TEXT, rowid INTEGER) records – exactly the schema of
-    /// `idx_companies_country` used by the challenge.
-    pub fn lookup_rowids_by_country(
+    /// Collect all rowids whose leading index columns equal `prefix`.
+    ///
+    /// For an index on `(state, city)`, `["CA"]` finds every row in
+    /// California and `["CA", "Oakland"]` only the ones in Oakland. The
+    /// rowids come back in index order.
+    pub fn lookup_rowids(
         &self,
-        db_path: &str,
         index_root_page: usize,
-        target_country: &str,
-    ) -> anyhow::Result<Vec<u64>> {
-        let mut rowids = Vec::new();
-        self.traverse_index(db_path, index_root_page, target_country, &mut rowids)?;
-        Ok(rowids)
+        prefix: &[RecordValue],
+    ) -> Result<Vec<i64>> {
+        self.index_cursor(index_root_page, prefix.to_vec())
+            .collect()
     }
 
-    fn traverse_index(
+    /// The same rowids as `lookup_rowids`, read from the index one at a
+    /// time.
+    pub(crate) fn index_cursor(
         &self,
-        db_path: &str,
-        page_num: usize,
-        target: &str,
-        rowids: &mut Vec<u64>,
-    ) -> anyhow::Result<()> {
-        let page = self.load_page(db_path, page_num)?;
-
-        match page.typ {
-            PageType::IndexLeaf => {
-                for (country, rowid) in page.index_leaf_entries() {
-                    match country.as_str().cmp(target) {
-                        std::cmp::Ordering::Less => continue, // still before our key
-                        std::cmp::Ordering::Equal => rowids.push(rowid),
-                        std::cmp::Ordering::Greater => break, // beyond target; no more matches in this leaf
-                    }
-                }
-            }
-            PageType::IndexInterior => {
-                // Fetch interior entries and determine which child(ren) to explore.
-                let entries = page.index_interior_entries();
-
-                // We'll iterate to decide which sub-trees can possibly hold the target.
-                for (i, (country_key, child_page)) in entries.iter().enumerate() {
-                    use std::cmp::Ordering::*;
-                    match target.cmp(country_key) {
-                        Less => {
-                            // Target lies entirely in left subtree (child_page)
-                            self.traverse_index(db_path, *child_page as usize, target, rowids)?;
-                            return Ok(());
-                        }
-                        Equal => {
-                            // Traverse matching child
-                            self.traverse_index(db_path, *child_page as usize, target, rowids)?;
-
-                            // Also traverse the immediate right sibling subtree because duplicates
-                            // could span boundaries.
-                            if i + 1 < entries.len() {
-                                let next_child = entries[i + 1].1;
-                                self.traverse_index(db_path, next_child as usize, target, rowids)?;
-                            } else if let Some(rightmost) = page.right_most_child {
-                                self.traverse_index(db_path, rightmost as usize, target, rowids)?;
-                            }
-                            return Ok(());
-                        }
-                        Greater => {
-                            // Keep scanning keys (*continue loop*)
-                        }
-                    }
-                }
-
-                // If we reach here, target > all keys – descend into rightmost child
-                if let Some(rightmost) = page.right_most_child {
-                    self.traverse_index(db_path, rightmost as usize, target, rowids)?;
-                }
-            }
-            _ => anyhow::bail!("Unexpected page type in index traversal: {:?}", page.typ),
-        }
-
-        Ok(())
+        index_root_page: usize,
+        prefix: Vec<RecordValue>,
+    ) -> IndexCursor<'_> {
+        IndexCursor::new(self, index_root_page, prefix)
     }
 
     /// Fetch a single table record by rowid via B-tree navigation.
     pub fn fetch_record_by_rowid(
         &self,
-        db_path: &str,
         table_root_page: usize,
-        rowid: u64,
-    ) -> anyhow::Result<Option<Record>> {
-        self.search_table_btree(db_path, table_root_page, rowid)
-    }
-
-    fn search_table_btree(
-        &self,
-        db_path: &str,
-        page_num: usize,
-        target_rowid: u64,
-    ) -> anyhow::Result<Option<Record>> {
-        let page = self.load_page(db_path, page_num)?;
-
-        match page.typ {
-            PageType::TableLeaf => {
-                for rec in page.records() {
-                    if rec.id == target_rowid {
-                        return Ok(Some(rec));
-                    }
-                }
-                Ok(None)
-            }
-            PageType::TableInterior => {
-                let entries = page.table_interior_entries();
-
-                // iterate over entries to decide which child to descend
-                for (i, (child_page, key_rowid)) in entries.iter().enumerate() {
-                    if target_rowid < *key_rowid {
-                        return self.search_table_btree(
-                            db_path,
-                            *child_page as usize,
-                            target_rowid,
-                        );
-                    } else if target_rowid == *key_rowid {
-                        // The row could be in left child or in the leaf page pointed by key? For table interior, exact key is not stored in child, row lives in left child.
-                        return self.search_table_btree(
-                            db_path,
-                            *child_page as usize,
-                            target_rowid,
-                        );
-                    }
-                    // else continue loop
-                }
-
-                // If not found among keys, descend into rightmost child
-                if let Some(rightmost) = page.right_most_child {
-                    self.search_table_btree(db_path, rightmost as usize, target_rowid)
-                } else {
-                    Ok(None)
-                }
-            }
-            _ => anyhow::bail!(
-                "Unexpected page type while searching table btree: {:?}",
-                page.typ
-            ),
+        rowid: i64,
+    ) -> Result<Option<Record>> {
+        match self.table_cursor(table_root_page).seek(rowid) {
+            Some(Ok(record)) if record.id == rowid => Ok(Some(record)),
+            Some(Err(err)) => Err(err),
+            _ => Ok(None),
         }
     }
 
     /// Fetch multiple records by ascending rowids list, preserving order.
     pub fn fetch_records_by_rowids(
         &self,
-        db_path: &str,
         table_root_page: usize,
-        rowids: &[u64],
-    ) -> anyhow::Result<Vec<Record>> {
+        rowids: &[i64],
+    ) -> Result<Vec<Record>> {
         let mut results = Vec::with_capacity(rowids.len());
         for &rid in rowids {
-            if let Some(rec) = self.fetch_record_by_rowid(db_path, table_root_page, rid)? {
+            if let Some(rec) = self.fetch_record_by_rowid(table_root_page, rid)? {
                 results.push(rec);
             }
         }
         Ok(results)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    // Build a record from (serial type, body bytes) pairs. Serial types are
+    // all < 128 here, so each one is a single header byte.
+    fn record(columns: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let header_size = 1 + columns.len() as u8;
+        let mut data = vec![header_size];
+        data.extend(columns.iter().map(|(serial, _)| *serial));
+        for (_, body) in columns {
+            data.extend(body);
+        }
+        data
+    }
+
+    // The low `width` bytes of `value`, big-endian, as the file stores them.
+    fn int_column(value: i64) -> (u8, Vec<u8>) {
+        let (serial, width) = match value {
+            -0x80..=0x7F => (1, 1),
+            -0x8000..=0x7FFF => (2, 2),
+            -0x80_0000..=0x7F_FFFF => (3, 3),
+            -0x8000_0000..=0x7FFF_FFFF => (4, 4),
+            -0x8000_0000_0000..=0x7FFF_FFFF_FFFF => (5, 6),
+            _ => (6, 8),
+        };
+        (serial, value.to_be_bytes()[8 - width..].to_vec())
+    }
+
+    const BOUNDARIES: &[i64] = &[
+        0,
+        1,
+        -1,
+        i8::MIN as i64,
+        i8::MAX as i64,
+        i16::MIN as i64,
+        i16::MAX as i64,
+        -(1 << 23),
+        (1 << 23) - 1,
+        i32::MIN as i64,
+        i32::MAX as i64,
+        -(1 << 47),
+        (1 << 47) - 1,
+        i64::MIN,
+        i64::MAX,
+    ];
+
+    #[test]
+    fn decodes_integer_boundaries_of_every_width() {
+        for &value in BOUNDARIES {
+            let data = record(&[int_column(value)]);
+            let values = Page::parse_record_values(&data, TextEncoding::Utf8).unwrap();
+            assert_eq!(values, vec![RecordValue::Int(value)], "value {}", value);
+        }
+    }
+
+    #[test]
+    fn decodes_negative_values_stored_in_wider_types() {
+        // A writer may use a wider type than needed; -1 is all 0xFF bytes.
+        for (serial, width) in [(1, 1), (2, 2), (3, 3), (4, 4), (5, 6), (6, 8)] {
+            let data = record(&[(serial, vec![0xFF; width])]);
+            let values = Page::parse_record_values(&data, TextEncoding::Utf8).unwrap();
+            assert_eq!(values, vec![RecordValue::Int(-1)], "serial type {}", serial);
+        }
+    }
+
+    #[test]
+    fn decodes_a_record_mixing_all_widths() {
+        let mut columns: Vec<_> = BOUNDARIES.iter().map(|&v| int_column(v)).collect();
+        columns.push((0, Vec::new())); // NULL
+        columns.push((8, Vec::new())); // constant 0
+        columns.push((9, Vec::new())); // constant 1
+        columns.push((7, (-2.5f64).to_be_bytes().to_vec()));
+        let data = record(&columns);
+
+        let values = Page::parse_record_values(&data, TextEncoding::Utf8).unwrap();
+        let mut expected: Vec<_> = BOUNDARIES.iter().map(|&v| RecordValue::Int(v)).collect();
+        expected.extend([
+            RecordValue::Null,
+            RecordValue::Int(0),
+            RecordValue::Int(1),
+            RecordValue::Real(-2.5),
+        ]);
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn decodes_text_in_the_database_encoding() {
+        let text = "Ā😀";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        for (encoding, bytes) in [
+            (TextEncoding::Utf8, text.as_bytes().to_vec()),
+            (TextEncoding::Utf16le, le),
+            (TextEncoding::Utf16be, be),
+        ] {
+            // Serial type 13 + 2n is TEXT of n bytes.
+            let data = record(&[(13 + 2 * bytes.len() as u8, bytes)]);
+            let values = Page::parse_record_values(&data, encoding).unwrap();
+            assert_eq!(
+                values,
+                [RecordValue::Text(text.to_string())],
+                "{:?}",
+                encoding
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_reserved_serial_types() {
+        for serial in [10, 11] {
+            let data = record(&[int_column(7), (serial, Vec::new())]);
+            assert_eq!(
+                Page::parse_record_values(&data, TextEncoding::Utf8),
+                Err(RecordError::InvalidSerialType(serial as u64))
+            );
+        }
+    }
+
+    #[test]
+    fn reads_multi_byte_serial_types() {
+        // 100 bytes of text is serial type 213, a two-byte varint.
+        let text = "x".repeat(100);
+        let mut data = vec![3];
+        varint::write(13 + 2 * 100, &mut data);
+        data.extend(text.as_bytes());
+        assert_eq!(
+            Page::parse_record_values(&data, TextEncoding::Utf8),
+            Ok(vec![RecordValue::Text(text)])
+        );
+
+        // A header that ends in the middle of a serial type.
+        assert_eq!(
+            Page::parse_record_values(&[2, 0x81], TextEncoding::Utf8),
+            Err(RecordError::Truncated)
+        );
+    }
+
+    #[test]
+    fn rejects_records_shorter_than_their_header() {
+        let mut data = record(&[int_column(1 << 20), (13 + 2 * 10, b"ten bytes!".to_vec())]);
+        data.truncate(data.len() - 1);
+        assert_eq!(
+            Page::parse_record_values(&data, TextEncoding::Utf8),
+            Err(RecordError::Truncated)
+        );
+    }
+
+    /// Overflow pages for hand-built pages, counting how often one is read.
+    #[derive(Default)]
+    struct Pages {
+        pages: HashMap<usize, Rc<[u8]>>,
+        reads: std::cell::Cell<usize>,
+    }
+
+    impl PageSource for Pages {
+        fn page_data(&self, page_number: usize) -> Result<Rc<[u8]>> {
+            self.reads.set(self.reads.get() + 1);
+            self.pages
+                .get(&page_number)
+                .cloned()
+                .ok_or(Error::PageOutOfRange {
+                    page: page_number,
+                    page_count: self.pages.len(),
+                })
+        }
+    }
+
+    #[test]
+    fn reads_overflow_pages_only_when_the_cell_is_decoded() {
+        // 600 bytes of text make a 603-byte payload. On a 512-byte table
+        // leaf 95 bytes stay local and the other 508 fill overflow page 2.
+        let text: Vec<u8> = (0..600).map(|i| b'a' + (i % 26) as u8).collect();
+        let mut payload = vec![3, 0x89, 0x3D]; // header: size 3, serial type 1213
+        payload.extend_from_slice(&text);
+
+        let mut cell = vec![0x84, 0x5B, 1]; // payload size 603, rowid 1
+        cell.extend_from_slice(&payload[..95]);
+        cell.extend_from_slice(&2u32.to_be_bytes());
+        let pointer = 512 - cell.len();
+        let mut leaf = vec![0; 512];
+        leaf[0] = 13;
+        leaf[3..5].copy_from_slice(&1u16.to_be_bytes());
+        leaf[8..10].copy_from_slice(&(pointer as u16).to_be_bytes());
+        leaf[pointer..].copy_from_slice(&cell);
+
+        let mut overflow = vec![0; 4]; // no next page
+        overflow.extend_from_slice(&payload[95..]);
+        let pages = Pages {
+            pages: HashMap::from([(2, overflow.into())]),
+            ..Pages::default()
+        };
+
+        let page = Page::from_data(5, 512, 512, TextEncoding::Utf8, leaf.into()).unwrap();
+        assert_eq!(page.rowid_at(0), 1);
+        assert_eq!(pages.reads.get(), 0);
+
+        let record = page.record_at(0, &pages).unwrap();
+        assert_eq!(
+            record.values,
+            vec![RecordValue::Text(String::from_utf8(text).unwrap())]
+        );
+        assert_eq!(pages.reads.get(), 1);
+    }
+
+    #[test]
+    fn reports_where_a_bad_serial_type_sits() {
+        // A table leaf with one cell (payload size, rowid, record) at the
+        // very end of a 512-byte page.
+        let cell = [4, 1, 3, 1, 10, 7];
+        let pointer = 512 - cell.len();
+        let mut data = vec![0; 512];
+        data[0] = 13;
+        data[3..5].copy_from_slice(&1u16.to_be_bytes());
+        data[8..10].copy_from_slice(&(pointer as u16).to_be_bytes());
+        data[pointer..].copy_from_slice(&cell);
+
+        let page = Page::from_data(3, 512, 512, TextEncoding::Utf8, data.into()).unwrap();
+        match page.record_at(0, &Pages::default()) {
+            Err(Error::InvalidSerialType {
+                page: 3,
+                offset,
+                serial_type: 10,
+            }) => assert_eq!(offset, pointer),
+            other => panic!("expected an invalid serial type error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_page_types() {
+        let mut data = vec![0; 512];
+        data[0] = 7;
+        match Page::from_data(4, 512, 512, TextEncoding::Utf8, data.into()) {
+            Err(Error::CorruptPage {
+                page: 4, offset: 0, ..
+            }) => {}
+            other => panic!("expected a corrupt page error, got {:?}", other),
+        }
+    }
+}
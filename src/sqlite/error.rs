@@ -0,0 +1,135 @@
+//! # sqlite/error.rs – everything that can go wrong reading a database
+//!
+//! ```text
+//!  file ──io──▶ header ──▶ page N ──▶ cell @ offset ──▶ record
+//!   Io        InvalidHeader  PageOutOfRange  CorruptPage  InvalidSerialType
+//! ```
+//!
+//! Decoding never panics on bad bytes: every problem comes back as an
+//! `Error` that says which page (and where on it) things went wrong.
+//! Offsets are byte offsets from the start of the page, as in the file
+//! format spec, so they can be checked against a hex dump.
+//!
+use thiserror::Error;
+
+use super::sql::ParseError;
+
+/// Why opening a database or running a query failed.
+///
+/// New variants may be added in minor releases, so matches need a
+/// wildcard arm.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading the file failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// The first 100 bytes don't describe a database we can read.
+    #[error("invalid database header: {0}")]
+    InvalidHeader(String),
+
+    /// A B-tree or overflow chain points past the end of the file.
+    #[error("page {page} is out of range (the database has {page_count} pages)")]
+    PageOutOfRange { page: usize, page_count: usize },
+
+    /// A page's bytes don't follow the file format.
+    #[error("page {page} is corrupt at offset {offset}: {reason}")]
+    CorruptPage {
+        page: usize,
+        offset: usize,
+        reason: String,
+    },
+
+    /// A record uses serial type 10 or 11, which are reserved.
+    #[error("invalid serial type {serial_type} in the record at offset {offset} of page {page}")]
+    InvalidSerialType {
+        page: usize,
+        offset: usize,
+        serial_type: u64,
+    },
+
+    /// The CREATE TABLE statement in sqlite_schema for the table being
+    /// queried doesn't parse. The rest of the schema is still usable.
+    #[error("malformed database schema ({name}) - {reason}")]
+    MalformedSchema { name: String, reason: String },
+
+    /// Another connection restarted the write-ahead log after we opened the
+    /// database, so the frames of our snapshot are gone. Reopen to read the
+    /// current state.
+    #[error("the write-ahead log was reset while reading page {page}; reopen the database")]
+    StaleSnapshot { page: usize },
+
+    /// The SQL text doesn't parse.
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+
+    /// The statement parsed but can't run: an unknown table or column, a
+    /// misused aggregate, a statement that would write, …
+    #[error(transparent)]
+    Query(#[from] QueryError),
+}
+
+/// Why a statement that parsed can't run, or stopped part way through.
+///
+/// New variants may be added in minor releases, so matches need a
+/// wildcard arm.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum QueryError {
+    /// FROM, or a `table.*` in the select list, names a table that isn't
+    /// there.
+    #[error("no such table: {0}")]
+    NoSuchTable(String),
+
+    /// The column as written, `table.column` when it was qualified.
+    #[error("no such column: {0}")]
+    NoSuchColumn(String),
+
+    /// An unqualified column name that more than one table in FROM has.
+    #[error("ambiguous column name: {0}")]
+    AmbiguousColumn(String),
+
+    #[error("no such function: {0}")]
+    NoSuchFunction(String),
+
+    #[error("wrong number of arguments to function {0}()")]
+    WrongArgumentCount(String),
+
+    /// An aggregate inside another one, or in WHERE.
+    #[error("misuse of aggregate function {0}()")]
+    MisusedAggregate(String),
+
+    /// `sum()` of integers went past the range of a 64-bit integer.
+    #[error("integer overflow")]
+    IntegerOverflow,
+
+    /// `ORDER BY 3` or `GROUP BY 3` with fewer result columns than that.
+    #[error("{clause} term out of range: {position}")]
+    TermOutOfRange { clause: &'static str, position: i64 },
+
+    /// A value of the wrong type where only one will do, such as a LIMIT
+    /// that isn't an integer.
+    #[error("datatype mismatch: {0}")]
+    DatatypeMismatch(String),
+
+    /// CREATE TABLE, CREATE INDEX and anything else that would write.
+    #[error("This database is read-only")]
+    ReadOnly,
+
+    /// Valid SQL this reader doesn't handle, e.g. WITHOUT ROWID tables.
+    #[error("{0}")]
+    Unsupported(String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub(crate) fn corrupt(page: usize, offset: usize, reason: impl Into<String>) -> Self {
+        Self::CorruptPage {
+            page,
+            offset,
+            reason: reason.into(),
+        }
+    }
+}
@@ -0,0 +1,640 @@
+//! # sqlite/eval.rs – evaluate an `Expr` against one row
+//!
+//! ```text
+//!  Expr ──evaluate(scope)──▶ RecordValue
+//!   │                           ▲
+//!   └─ Column "x" ──scope.column()
+//! ```
+//!
+//! Follows SQLite's rules where they matter for filtering:
+//! – three-valued logic: anything compared with NULL is NULL, and
+//!   `NULL AND 0` is 0 while `NULL AND 1` is NULL;
+//! – comparisons use type order NULL < numbers < TEXT < BLOB, with numbers
+//!   compared numerically;
+//! – a column with numeric affinity compared to a text operand converts
+//!   the text to a number first (so `age = '30'` finds 30);
+//! – arithmetic on integers stays integer until it overflows, division by
+//!   zero is NULL, and `||` joins the text forms of both sides.
+//!
+use std::cmp::Ordering;
+
+use super::aggregate;
+use super::error::{QueryError, Result};
+use super::sql::{BinaryOp, Expr, Literal, UnaryOp};
+use super::{RecordValue, TextEncoding};
+
+/// Column affinity, derived from the declared type in CREATE TABLE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affinity {
+    Integer,
+    Real,
+    Numeric,
+    Text,
+    Blob,
+}
+
+impl Affinity {
+    /// The "determination of column affinity" rules from the SQLite docs,
+    /// checked in order.
+    pub fn from_declared_type(declared: &str) -> Self {
+        let declared = declared.to_ascii_uppercase();
+        if declared.contains("INT") {
+            Affinity::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|t| declared.contains(t))
+        {
+            Affinity::Text
+        } else if declared.is_empty() || declared.contains("BLOB") {
+            Affinity::Blob
+        } else if ["REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|t| declared.contains(t))
+        {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(self, Affinity::Integer | Affinity::Real | Affinity::Numeric)
+    }
+
+    /// Convert a value the way storing it in a column of this affinity would.
+    pub fn apply(self, value: RecordValue) -> RecordValue {
+        match self {
+            Affinity::Integer | Affinity::Real | Affinity::Numeric => apply_numeric_affinity(value),
+            Affinity::Text => apply_text_affinity(value),
+            Affinity::Blob => value,
+        }
+    }
+}
+
+/// Where column references get their values from.
+pub trait Scope {
+    fn column(&self, table: Option<&str>, name: &str) -> Result<RecordValue>;
+
+    /// Affinity of a column, if the scope knows its declared type.
+    fn affinity(&self, _table: Option<&str>, _name: &str) -> Option<Affinity> {
+        None
+    }
+
+    /// Final value of an aggregate call such as `count(*)`, for scopes that
+    /// stand for a whole group of rows. Plain rows have none.
+    fn aggregate(&self, _call: &Expr) -> Option<RecordValue> {
+        None
+    }
+
+    /// The database's text encoding; text compares byte-wise in it.
+    fn encoding(&self) -> TextEncoding {
+        TextEncoding::Utf8
+    }
+}
+
+/// Scope for constant expressions (e.g. LIMIT): any column is an error.
+pub struct NoColumns;
+
+impl Scope for NoColumns {
+    fn column(&self, _table: Option<&str>, name: &str) -> Result<RecordValue> {
+        Err(QueryError::NoSuchColumn(name.to_string()).into())
+    }
+}
+
+pub fn evaluate(expr: &Expr, scope: &dyn Scope) -> Result<RecordValue> {
+    Ok(match expr {
+        Expr::Literal(literal) => literal_value(literal),
+        Expr::Column { table, name } => scope.column(table.as_deref(), name)?,
+        Expr::Unary { op, expr } => {
+            let value = evaluate(expr, scope)?;
+            match op {
+                UnaryOp::Not => match truthiness(&value) {
+                    Some(b) => bool_value(!b),
+                    None => RecordValue::Null,
+                },
+                UnaryOp::Negate => match to_numeric(&value) {
+                    RecordValue::Int(n) => match n.checked_neg() {
+                        Some(n) => RecordValue::Int(n),
+                        None => RecordValue::Real(-(n as f64)),
+                    },
+                    RecordValue::Real(f) => RecordValue::Real(-f),
+                    other => other,
+                },
+                UnaryOp::Plus => value,
+            }
+        }
+        Expr::Binary { left, op, right } => match op {
+            BinaryOp::And => {
+                // Short-circuit on a definite false; NULL only wins over true.
+                let l = truthiness(&evaluate(left, scope)?);
+                if l == Some(false) {
+                    return Ok(bool_value(false));
+                }
+                match (l, truthiness(&evaluate(right, scope)?)) {
+                    (_, Some(false)) => bool_value(false),
+                    (Some(true), Some(true)) => bool_value(true),
+                    _ => RecordValue::Null,
+                }
+            }
+            BinaryOp::Or => {
+                let l = truthiness(&evaluate(left, scope)?);
+                if l == Some(true) {
+                    return Ok(bool_value(true));
+                }
+                match (l, truthiness(&evaluate(right, scope)?)) {
+                    (_, Some(true)) => bool_value(true),
+                    (Some(false), Some(false)) => bool_value(false),
+                    _ => RecordValue::Null,
+                }
+            }
+            BinaryOp::Concat => match (evaluate(left, scope)?, evaluate(right, scope)?) {
+                (RecordValue::Null, _) | (_, RecordValue::Null) => RecordValue::Null,
+                (l, r) => RecordValue::Text(text_value(&l) + &text_value(&r)),
+            },
+            BinaryOp::Add
+            | BinaryOp::Subtract
+            | BinaryOp::Multiply
+            | BinaryOp::Divide
+            | BinaryOp::Remainder => {
+                arithmetic(&evaluate(left, scope)?, *op, &evaluate(right, scope)?)
+            }
+            _ => comparison(left, *op, right, scope)?,
+        },
+        Expr::IsNull { expr, negated } => {
+            let is_null = matches!(evaluate(expr, scope)?, RecordValue::Null);
+            bool_value(is_null != *negated)
+        }
+        Expr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => {
+            // `x BETWEEN a AND b` is `x >= a AND x <= b`.
+            let above = truthiness(&comparison(expr, BinaryOp::GtEq, low, scope)?);
+            let below = truthiness(&comparison(expr, BinaryOp::LtEq, high, scope)?);
+            match (above, below) {
+                (Some(false), _) | (_, Some(false)) => bool_value(*negated),
+                (Some(true), Some(true)) => bool_value(!*negated),
+                _ => RecordValue::Null,
+            }
+        }
+        Expr::Function { name, .. } => match scope.aggregate(expr) {
+            Some(value) => value,
+            None if aggregate::is_aggregate(expr) => {
+                return Err(QueryError::MisusedAggregate(name.clone()).into())
+            }
+            None => return Err(QueryError::NoSuchFunction(name.clone()).into()),
+        },
+    })
+}
+
+/// `left op right` for one of the comparison operators.
+fn comparison(left: &Expr, op: BinaryOp, right: &Expr, scope: &dyn Scope) -> Result<RecordValue> {
+    let (l, r) = comparison_operands(left, right, scope)?;
+    Ok(match compare(&l, &r, scope.encoding()) {
+        None => RecordValue::Null,
+        Some(ordering) => bool_value(match op {
+            BinaryOp::Eq => ordering == Ordering::Equal,
+            BinaryOp::NotEq => ordering != Ordering::Equal,
+            BinaryOp::Lt => ordering == Ordering::Less,
+            BinaryOp::LtEq => ordering != Ordering::Greater,
+            BinaryOp::Gt => ordering == Ordering::Greater,
+            BinaryOp::GtEq => ordering != Ordering::Less,
+            _ => unreachable!("{:?} is not a comparison", op),
+        }),
+    })
+}
+
+/// `left op right` for `+ - * / %`. Both sides are taken as numbers; integer
+/// results that overflow become reals, and dividing by zero gives NULL.
+fn arithmetic(left: &RecordValue, op: BinaryOp, right: &RecordValue) -> RecordValue {
+    let (l, r) = match (to_numeric(left), to_numeric(right)) {
+        (RecordValue::Null, _) | (_, RecordValue::Null) => return RecordValue::Null,
+        pair => pair,
+    };
+    if let (RecordValue::Int(a), RecordValue::Int(b)) = (&l, &r) {
+        let (a, b) = (*a, *b);
+        let exact = match op {
+            BinaryOp::Add => a.checked_add(b),
+            BinaryOp::Subtract => a.checked_sub(b),
+            BinaryOp::Multiply => a.checked_mul(b),
+            BinaryOp::Divide | BinaryOp::Remainder if b == 0 => return RecordValue::Null,
+            // i64::MIN / -1 is the only quotient that overflows.
+            BinaryOp::Divide => a.checked_div(b),
+            BinaryOp::Remainder => Some(a.wrapping_rem(b)),
+            _ => unreachable!("{:?} is not arithmetic", op),
+        };
+        if let Some(n) = exact {
+            return RecordValue::Int(n);
+        }
+    }
+
+    let (a, b) = (as_real(&l), as_real(&r));
+    let result = match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Subtract => a - b,
+        BinaryOp::Multiply => a * b,
+        BinaryOp::Divide if b == 0.0 => return RecordValue::Null,
+        BinaryOp::Divide => a / b,
+        BinaryOp::Remainder => {
+            // SQLite takes the remainder of the integer parts.
+            let (a, b) = (a as i64, b as i64);
+            if b == 0 {
+                return RecordValue::Null;
+            }
+            a.wrapping_rem(b) as f64
+        }
+        _ => unreachable!("{:?} is not arithmetic", op),
+    };
+    if result.is_nan() {
+        RecordValue::Null
+    } else {
+        RecordValue::Real(result)
+    }
+}
+
+fn as_real(value: &RecordValue) -> f64 {
+    match value {
+        RecordValue::Int(n) => *n as f64,
+        RecordValue::Real(f) => *f,
+        _ => 0.0,
+    }
+}
+
+/// Text form of a non-NULL value, as `||` sees it.
+fn text_value(value: &RecordValue) -> String {
+    match value {
+        RecordValue::Int(n) => n.to_string(),
+        RecordValue::Real(f) => format_real(*f),
+        RecordValue::Text(text) => text.clone(),
+        RecordValue::Blob(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        RecordValue::Null => String::new(),
+    }
+}
+
+/// Evaluate a WHERE-style condition: only a true result keeps the row.
+pub fn is_true(expr: &Expr, scope: &dyn Scope) -> Result<bool> {
+    Ok(truthiness(&evaluate(expr, scope)?) == Some(true))
+}
+
+/// SQLite's notion of truth: NULL is unknown, numbers are true when non-zero,
+/// text and blobs are converted to a number first.
+pub fn truthiness(value: &RecordValue) -> Option<bool> {
+    match to_numeric(value) {
+        RecordValue::Null => None,
+        RecordValue::Int(n) => Some(n != 0),
+        RecordValue::Real(f) => Some(f != 0.0),
+        _ => Some(false),
+    }
+}
+
+/// Compare two values, text in the order of `encoding`; `None` when either
+/// side is NULL.
+pub fn compare(
+    left: &RecordValue,
+    right: &RecordValue,
+    encoding: TextEncoding,
+) -> Option<Ordering> {
+    if matches!(left, RecordValue::Null) || matches!(right, RecordValue::Null) {
+        return None;
+    }
+    Some(left.sqlite_cmp_in(right, encoding))
+}
+
+pub fn literal_value(literal: &Literal) -> RecordValue {
+    match literal {
+        Literal::Null => RecordValue::Null,
+        Literal::Integer(number) => RecordValue::Int(*number),
+        Literal::Real(float) => RecordValue::Real(*float),
+        Literal::String(text) => RecordValue::Text(text.clone()),
+        Literal::Blob(bytes) => RecordValue::Blob(bytes.clone()),
+    }
+}
+
+fn bool_value(b: bool) -> RecordValue {
+    RecordValue::Int(b as i64)
+}
+
+/// Only column references carry an affinity; everything else has none.
+fn expr_affinity(expr: &Expr, scope: &dyn Scope) -> Option<Affinity> {
+    match expr {
+        Expr::Column { table, name } => scope.affinity(table.as_deref(), name),
+        _ => None,
+    }
+}
+
+/// Evaluate both sides of a comparison and apply affinity conversions:
+/// numeric affinity on one side converts text on the other side to a number,
+/// text affinity converts a number without affinity to text.
+fn comparison_operands(
+    left: &Expr,
+    right: &Expr,
+    scope: &dyn Scope,
+) -> Result<(RecordValue, RecordValue)> {
+    let mut l = evaluate(left, scope)?;
+    let mut r = evaluate(right, scope)?;
+    let (la, ra) = (expr_affinity(left, scope), expr_affinity(right, scope));
+
+    let numeric = |a: Option<Affinity>| a.is_some_and(Affinity::is_numeric);
+    let textish = |a: Option<Affinity>| matches!(a, None | Some(Affinity::Text | Affinity::Blob));
+
+    if numeric(la) && textish(ra) {
+        r = apply_numeric_affinity(r);
+    } else if numeric(ra) && textish(la) {
+        l = apply_numeric_affinity(l);
+    } else if la == Some(Affinity::Text) && ra.is_none() {
+        r = apply_text_affinity(r);
+    } else if ra == Some(Affinity::Text) && la.is_none() {
+        l = apply_text_affinity(l);
+    }
+    Ok((l, r))
+}
+
+/// Text that looks exactly like a number becomes that number.
+fn apply_numeric_affinity(value: RecordValue) -> RecordValue {
+    if let RecordValue::Text(text) = &value {
+        let trimmed = text.trim();
+        if let Ok(n) = trimmed.parse::<i64>() {
+            return RecordValue::Int(n);
+        }
+        if let Ok(f) = trimmed.parse::<f64>() {
+            if f.is_finite() && !trimmed.is_empty() {
+                return RecordValue::Real(f);
+            }
+        }
+    }
+    value
+}
+
+fn apply_text_affinity(value: RecordValue) -> RecordValue {
+    match value {
+        RecordValue::Int(n) => RecordValue::Text(n.to_string()),
+        RecordValue::Real(f) => RecordValue::Text(format_real(f)),
+        other => other,
+    }
+}
+
+/// Render a real the way SQLite does (`printf("%!.15g")`): 15 significant
+/// digits, and always a decimal point so it reads back as a real.
+///
+///   3.0 → "3.0"   0.1 + 0.2 → "0.3"   1e20 → "1.0e+20"
+pub fn format_real(f: f64) -> String {
+    if f.is_nan() {
+        return String::new();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "Inf" } else { "-Inf" }.to_string();
+    }
+    if f == 0.0 {
+        return "0.0".to_string(); // also for -0.0
+    }
+
+    // `{:.14e}` rounds to 15 significant digits: "d.dddddddddddddde±x".
+    let scientific = format!("{:.14e}", f);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let (negative, mantissa) = match mantissa.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, mantissa),
+    };
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    let digits = digits.trim_end_matches('0');
+    let digits = if digits.is_empty() { "0" } else { digits };
+
+    let mut out = String::new();
+    if negative {
+        out.push('-');
+    }
+    if !(-4..15).contains(&exponent) {
+        out.push_str(&digits[..1]);
+        out.push('.');
+        out.push_str(if digits.len() > 1 { &digits[1..] } else { "0" });
+        out.push_str(&format!(
+            "e{}{:02}",
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        ));
+    } else if exponent < 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat((-exponent - 1) as usize));
+        out.push_str(digits);
+    } else {
+        let int_len = exponent as usize + 1;
+        if digits.len() > int_len {
+            out.push_str(&digits[..int_len]);
+            out.push('.');
+            out.push_str(&digits[int_len..]);
+        } else {
+            out.push_str(digits);
+            out.push_str(&"0".repeat(int_len - digits.len()));
+            out.push_str(".0");
+        }
+    }
+    out
+}
+
+/// Numeric value of anything: text and blobs use their longest numeric
+/// prefix (`'12abc'` → 12, `'abc'` → 0), NULL stays NULL.
+pub fn to_numeric(value: &RecordValue) -> RecordValue {
+    let text = match value {
+        RecordValue::Null | RecordValue::Int(_) | RecordValue::Real(_) => return value.clone(),
+        RecordValue::Text(text) => text.clone(),
+        RecordValue::Blob(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    let text = text.trim_start();
+
+    // Longest prefix that still parses: digits, one dot, an exponent.
+    let bytes = text.as_bytes();
+    let mut end = 0;
+    if matches!(bytes.first(), Some(b'+' | b'-')) {
+        end = 1;
+    }
+    let digits_start = end;
+    while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+        end += 1;
+    }
+    let mut is_real = false;
+    if bytes.get(end) == Some(&b'.') {
+        is_real = true;
+        end += 1;
+        while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+            end += 1;
+        }
+    }
+    if end == digits_start || (is_real && end == digits_start + 1) {
+        return RecordValue::Int(0);
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let mut exp_end = end + 1;
+        if matches!(bytes.get(exp_end), Some(b'+' | b'-')) {
+            exp_end += 1;
+        }
+        if bytes.get(exp_end).is_some_and(u8::is_ascii_digit) {
+            while bytes.get(exp_end).is_some_and(u8::is_ascii_digit) {
+                exp_end += 1;
+            }
+            end = exp_end;
+            is_real = true;
+        }
+    }
+
+    let number = &text[..end];
+    if !is_real {
+        if let Ok(n) = number.parse::<i64>() {
+            return RecordValue::Int(n);
+        }
+    }
+    RecordValue::Real(number.parse::<f64>().unwrap_or(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::super::sql::{self, ResultColumn, Statement};
+    use super::*;
+
+    /// A row with typed columns: name → (value, affinity).
+    struct Row(HashMap<&'static str, (RecordValue, Affinity)>);
+
+    impl Scope for Row {
+        fn column(&self, _table: Option<&str>, name: &str) -> Result<RecordValue> {
+            match self.0.get(name) {
+                Some((value, _)) => Ok(value.clone()),
+                None => Err(QueryError::NoSuchColumn(name.to_string()).into()),
+            }
+        }
+
+        fn affinity(&self, _table: Option<&str>, name: &str) -> Option<Affinity> {
+            self.0.get(name).map(|(_, affinity)| *affinity)
+        }
+    }
+
+    fn people() -> Row {
+        Row(HashMap::from([
+            ("age", (RecordValue::Int(30), Affinity::Integer)),
+            (
+                "code",
+                (RecordValue::Text("30".to_string()), Affinity::Text),
+            ),
+        ]))
+    }
+
+    fn eval(expr: &str, scope: &dyn Scope) -> RecordValue {
+        let Ok(Statement::Select(select)) = sql::parse(&format!("SELECT {} FROM t", expr)) else {
+            panic!("did not parse: {}", expr);
+        };
+        let ResultColumn::Expr { expr, .. } = &select.columns[0] else {
+            panic!("not an expression: {:?}", select.columns[0]);
+        };
+        evaluate(expr, scope).unwrap()
+    }
+
+    #[test]
+    fn and_or_follow_three_valued_logic() {
+        let cases = [
+            ("NULL AND 0", RecordValue::Int(0)),
+            ("0 AND NULL", RecordValue::Int(0)),
+            ("NULL AND 1", RecordValue::Null),
+            ("NULL OR 1", RecordValue::Int(1)),
+            ("1 OR NULL", RecordValue::Int(1)),
+            ("NULL OR 0", RecordValue::Null),
+            ("NOT NULL", RecordValue::Null),
+            ("NULL = NULL", RecordValue::Null),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(eval(expr, &NoColumns), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn column_affinity_converts_the_other_operand() {
+        let row = people();
+        // Numeric column against text: the text becomes a number.
+        assert_eq!(eval("age = '30'", &row), RecordValue::Int(1));
+        assert_eq!(eval("age < '4'", &row), RecordValue::Int(0));
+        // Text column against a number: the number becomes text.
+        assert_eq!(eval("code = 30", &row), RecordValue::Int(1));
+        assert_eq!(eval("code < 4", &row), RecordValue::Int(1));
+        // Without a column there is no affinity, so types decide.
+        assert_eq!(eval("30 = '30'", &NoColumns), RecordValue::Int(0));
+    }
+
+    #[test]
+    fn between_with_a_null_bound_is_null_unless_the_other_bound_fails() {
+        let cases = [
+            ("5 BETWEEN NULL AND 10", RecordValue::Null),
+            ("5 BETWEEN NULL AND 3", RecordValue::Int(0)),
+            ("5 BETWEEN 6 AND NULL", RecordValue::Int(0)),
+            ("5 NOT BETWEEN NULL AND 3", RecordValue::Int(1)),
+            ("5 NOT BETWEEN NULL AND 10", RecordValue::Null),
+            ("NULL BETWEEN 1 AND 10", RecordValue::Null),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(eval(expr, &NoColumns), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn format_real_matches_sqlite() {
+        let cases = [
+            (3.0, "3.0"),
+            (0.1 + 0.2, "0.3"),
+            (1e20, "1.0e+20"),
+            (1e-5, "1.0e-05"),
+            (123456789012345678.0, "1.23456789012346e+17"),
+            (-0.5, "-0.5"),
+            (100.0, "100.0"),
+            (1e15, "1.0e+15"),
+            (1e14, "100000000000000.0"),
+            (2.5e-4, "0.00025"),
+            (1.0 / 3.0, "0.333333333333333"),
+            (-0.0, "0.0"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(format_real(value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn values_order_by_storage_class() {
+        use RecordValue::*;
+        let ascending = [
+            Int(i64::MIN),
+            Real(-1.5),
+            Int(2),
+            Real(2.5),
+            Int(i64::MAX),
+            Text(String::new()),
+            Text("1".to_string()),
+            Text("a".to_string()),
+            Blob(Vec::new()),
+            Blob(vec![0]),
+        ];
+        for pair in ascending.windows(2) {
+            assert_eq!(
+                compare(&pair[0], &pair[1], TextEncoding::Utf8),
+                Some(Ordering::Less),
+                "{:?}",
+                pair
+            );
+            assert_eq!(
+                compare(&pair[1], &pair[0], TextEncoding::Utf8),
+                Some(Ordering::Greater),
+                "{:?}",
+                pair
+            );
+        }
+        let utf8 = TextEncoding::Utf8;
+        assert_eq!(compare(&Int(2), &Real(2.0), utf8), Some(Ordering::Equal));
+        assert_eq!(compare(&Null, &Int(1), utf8), None);
+        assert_eq!(compare(&Text("x".to_string()), &Null, utf8), None);
+
+        // Text compares byte-wise in the database's encoding.
+        let (wide, b) = (Text("\u{100}".to_string()), Text("b".to_string()));
+        assert_eq!(compare(&wide, &b, utf8), Some(Ordering::Greater));
+        let utf16le = TextEncoding::Utf16le;
+        assert_eq!(compare(&wide, &b, utf16le), Some(Ordering::Less));
+    }
+}
@@ -0,0 +1,294 @@
+//! # sqlite/header.rs – the 100 bytes at the start of every database
+//!
+//! ```text
+//!  0      16   18 19 20 21-23   24      28      32      36      40
+//!  ┌──────┬────┬──┬──┬──┬──────┬───────┬───────┬───────┬───────┬─────┐
+//!  │magic │page│wr│rd│rs│64 32 │change │pages  │free   │free   │ …   │
+//!  │      │size│  │  │vd│32    │counter│       │trunk  │count  │     │
+//!  └──────┴────┴──┴──┴──┴──────┴───────┴───────┴───────┴───────┴─────┘
+//!  40 schema cookie   44 schema format   48 cache size   52 top root
+//!  56 text encoding   60 user version    64 incr vacuum  68 application id
+//!  92 version-valid-for                  96 SQLite version number
+//! ```
+//!
+//! Every multi-byte field is big-endian. Bytes 72–91 are reserved for
+//! expansion and must be zero. A page size of 65536 doesn't fit in two
+//! bytes and is stored as 1.
+//!
+use std::cmp::Ordering;
+
+use super::error::{Error, Result};
+
+pub const HEADER_SIZE: usize = 100;
+
+const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// How TEXT values are encoded throughout the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16le,
+    Utf16be,
+}
+
+impl TextEncoding {
+    /// The value stored at offset 56.
+    pub fn code(self) -> u32 {
+        match self {
+            TextEncoding::Utf8 => 1,
+            TextEncoding::Utf16le => 2,
+            TextEncoding::Utf16be => 3,
+        }
+    }
+
+    /// The name the sqlite3 shell prints, e.g. `utf16le`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf8",
+            TextEncoding::Utf16le => "utf16le",
+            TextEncoding::Utf16be => "utf16be",
+        }
+    }
+
+    /// Decode a TEXT value stored in this encoding. Invalid sequences
+    /// become U+FFFD; a stray odd byte at the end of UTF-16 is dropped, as
+    /// SQLite does.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let unit = |pair: &[u8]| match self {
+            TextEncoding::Utf16le => u16::from_le_bytes([pair[0], pair[1]]),
+            _ => u16::from_be_bytes([pair[0], pair[1]]),
+        };
+        match self {
+            TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            TextEncoding::Utf16le | TextEncoding::Utf16be => {
+                char::decode_utf16(bytes.chunks_exact(2).map(unit))
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect()
+            }
+        }
+    }
+
+    /// Order two strings the way the BINARY collation does in a database
+    /// of this encoding: `memcmp` over the stored bytes. That is code point
+    /// order for UTF-8, code unit order for UTF-16BE, and for UTF-16LE each
+    /// unit compares low byte first.
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            TextEncoding::Utf8 => a.as_bytes().cmp(b.as_bytes()),
+            TextEncoding::Utf16be => a.encode_utf16().cmp(b.encode_utf16()),
+            TextEncoding::Utf16le => a
+                .encode_utf16()
+                .map(u16::swap_bytes)
+                .cmp(b.encode_utf16().map(u16::swap_bytes)),
+        }
+    }
+}
+
+/// Every field of the database header, decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DatabaseHeader {
+    /// Page size in bytes, 512 to 65536. (Stored as 1 for 65536, which
+    /// doesn't fit in the two header bytes; this is the real size.)
+    pub page_size: u32,
+    /// File format write version: 1 = legacy rollback journal, 2 = WAL.
+    pub write_version: u8,
+    /// File format read version: 1 = legacy rollback journal, 2 = WAL.
+    pub read_version: u8,
+    /// Bytes at the end of each page set aside for extensions.
+    pub reserved_bytes: u8,
+    /// Payload fractions; always 64, 32 and 32.
+    pub max_payload_fraction: u8,
+    pub min_payload_fraction: u8,
+    pub leaf_payload_fraction: u8,
+    pub file_change_counter: u32,
+    /// Database size in pages, as recorded by the last writer. Only to be
+    /// trusted when `page_count_is_valid`.
+    pub page_count: u32,
+    pub freelist_trunk_page: u32,
+    pub freelist_page_count: u32,
+    pub schema_cookie: u32,
+    /// Schema format number, 1 to 4.
+    pub schema_format: u32,
+    pub default_cache_size: u32,
+    /// Largest root page when in auto-vacuum or incremental-vacuum mode,
+    /// 0 otherwise.
+    pub autovacuum_top_root: u32,
+    pub text_encoding: TextEncoding,
+    pub user_version: u32,
+    /// Non-zero for incremental-vacuum mode.
+    pub incremental_vacuum: u32,
+    pub application_id: u32,
+    /// The `file_change_counter` value `sqlite_version` belongs to.
+    pub version_valid_for: u32,
+    /// `SQLITE_VERSION_NUMBER` of the library that last wrote the file,
+    /// e.g. 3040001 for 3.40.1.
+    pub sqlite_version: u32,
+}
+
+impl DatabaseHeader {
+    pub fn parse(bytes: &[u8; HEADER_SIZE]) -> Result<Self> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(Error::InvalidHeader("not a SQLite 3 database".to_string()));
+        }
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        let page_size = match u16::from_be_bytes([bytes[16], bytes[17]]) {
+            1 => 65536,
+            size => size as u32,
+        };
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return Err(Error::InvalidHeader(format!(
+                "unsupported page size {}",
+                page_size
+            )));
+        }
+        let reserved_bytes = bytes[20];
+        if (page_size as usize) - (reserved_bytes as usize) < 480 {
+            return Err(Error::InvalidHeader(format!(
+                "{} reserved bytes leave too little room on each page",
+                reserved_bytes
+            )));
+        }
+        let text_encoding = match u32_at(56) {
+            1 => TextEncoding::Utf8,
+            2 => TextEncoding::Utf16le,
+            3 => TextEncoding::Utf16be,
+            other => {
+                return Err(Error::InvalidHeader(format!(
+                    "unknown text encoding {}",
+                    other
+                )))
+            }
+        };
+
+        Ok(Self {
+            page_size,
+            write_version: bytes[18],
+            read_version: bytes[19],
+            reserved_bytes,
+            max_payload_fraction: bytes[21],
+            min_payload_fraction: bytes[22],
+            leaf_payload_fraction: bytes[23],
+            file_change_counter: u32_at(24),
+            page_count: u32_at(28),
+            freelist_trunk_page: u32_at(32),
+            freelist_page_count: u32_at(36),
+            schema_cookie: u32_at(40),
+            schema_format: u32_at(44),
+            default_cache_size: u32_at(48),
+            autovacuum_top_root: u32_at(52),
+            text_encoding,
+            user_version: u32_at(60),
+            incremental_vacuum: u32_at(64),
+            application_id: u32_at(68),
+            version_valid_for: u32_at(92),
+            sqlite_version: u32_at(96),
+        })
+    }
+
+    /// Bytes of each page available to the B-tree.
+    pub fn usable_size(&self) -> usize {
+        self.page_size as usize - self.reserved_bytes as usize
+    }
+
+    /// The in-header page count is only kept up to date by writers since
+    /// 3.7.0; older ones leave `version_valid_for` behind the change counter.
+    pub fn page_count_is_valid(&self) -> bool {
+        self.page_count != 0 && self.version_valid_for == self.file_change_counter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_bytes() -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[..16].copy_from_slice(MAGIC);
+        bytes[16..18].copy_from_slice(&4096u16.to_be_bytes());
+        bytes[18] = 2;
+        bytes[19] = 2;
+        bytes[20] = 8;
+        bytes[21..24].copy_from_slice(&[64, 32, 32]);
+        for (offset, value) in [
+            (24, 7),
+            (28, 12),
+            (36, 3),
+            (40, 5),
+            (44, 4),
+            (56, 2),
+            (60, 42),
+            (68, 0x0F00_BA44),
+            (92, 7),
+            (96, 3_045_001),
+        ] {
+            bytes[offset..offset + 4].copy_from_slice(&(value as u32).to_be_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn decodes_every_field() {
+        let header = DatabaseHeader::parse(&header_bytes()).unwrap();
+        assert_eq!(header.page_size, 4096);
+        assert_eq!((header.write_version, header.read_version), (2, 2));
+        assert_eq!(header.reserved_bytes, 8);
+        assert_eq!(header.usable_size(), 4088);
+        assert_eq!(header.file_change_counter, 7);
+        assert_eq!(header.page_count, 12);
+        assert!(header.page_count_is_valid());
+        assert_eq!(header.freelist_page_count, 3);
+        assert_eq!(header.schema_cookie, 5);
+        assert_eq!(header.schema_format, 4);
+        assert_eq!(header.text_encoding, TextEncoding::Utf16le);
+        assert_eq!(header.user_version, 42);
+        assert_eq!(header.application_id, 0x0F00_BA44);
+        assert_eq!(header.sqlite_version, 3_045_001);
+    }
+
+    #[test]
+    fn reads_page_size_one_as_65536() {
+        let mut bytes = header_bytes();
+        bytes[16..18].copy_from_slice(&1u16.to_be_bytes());
+        let header = DatabaseHeader::parse(&bytes).unwrap();
+        assert_eq!(header.page_size, 65536);
+        assert_eq!(header.usable_size(), 65528);
+    }
+
+    #[test]
+    fn decodes_and_orders_text_in_every_encoding() {
+        let text = "añ😀";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(TextEncoding::Utf8.decode(text.as_bytes()), text);
+        assert_eq!(TextEncoding::Utf16le.decode(&le), text);
+        assert_eq!(TextEncoding::Utf16be.decode(&be), text);
+        assert_eq!(TextEncoding::Utf16be.decode(&be[..3]), "a");
+
+        // U+0100 is 00 01 in UTF-16LE, below "b" (62 00).
+        assert!(TextEncoding::Utf8.compare("\u{100}", "b").is_gt());
+        assert!(TextEncoding::Utf16be.compare("\u{100}", "b").is_gt());
+        assert!(TextEncoding::Utf16le.compare("\u{100}", "b").is_lt());
+        // Surrogates (D800..) sort below U+E000 in UTF-16, above it in UTF-8.
+        assert!(TextEncoding::Utf8.compare("😀", "\u{E000}").is_gt());
+        assert!(TextEncoding::Utf16be.compare("😀", "\u{E000}").is_lt());
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_databases() {
+        let mut bytes = header_bytes();
+        bytes[0] = b's';
+        assert!(matches!(
+            DatabaseHeader::parse(&bytes),
+            Err(Error::InvalidHeader(_))
+        ));
+    }
+}
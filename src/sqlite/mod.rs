@@ -1,3 +1,21 @@
+pub mod aggregate;
+mod cache;
+mod cursor;
 mod db;
+mod error;
+pub mod eval;
+mod header;
+mod query;
+mod schema;
+pub mod sql;
+mod varint;
+mod wal;
 
+pub use cache::CacheStats;
+pub use cursor::{RowidRange, TableCursor};
 pub use db::{Database, Record, RecordValue};
+pub use error::{Error, QueryError, Result};
+pub use eval::Affinity;
+pub use header::{DatabaseHeader, TextEncoding};
+pub use query::{Row, Rows};
+pub use schema::{Column, Index, IndexColumn, Schema, Table, Trigger, View};
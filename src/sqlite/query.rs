@@ -0,0 +1,1501 @@
+//! # sqlite/query.rs – run a SELECT and hand back its rows
+//!
+//! ```text
+//!  "SELECT …" ──sql::parse()──▶ Select ──plan──▶ index lookup / rowid order / scan
+//!                                                        │
+//!                                                        ▼
+//!    Rows ◀── ORDER BY, LIMIT ◀── GROUP BY, HAVING ◀── WHERE ◀── nested-loop JOIN
+//! ```
+//!
+//! `Database::query` is the entry point. Planning picks how to reach the
+//! rows of each table in FROM (a rowid seek, an index lookup or a full
+//! scan) and whether they already come out in ORDER BY order. Nothing is
+//! read until `Rows` is iterated: each call pulls the next joined row
+//! through WHERE and hands it out, so LIMIT stops the scan right there.
+//! Only grouping, and sorting that no index or rowid order provides,
+//! collect the whole scan first, inside the first call.
+//!
+use std::{
+    cmp::Ordering,
+    collections::{btree_map::Entry, BTreeMap},
+    fmt, iter,
+    ops::RangeInclusive,
+    vec,
+};
+
+use super::aggregate::{self, Accumulator};
+use super::error::{Error, QueryError, Result};
+use super::eval::{self, Affinity, Scope};
+use super::schema::SCHEMA_ROOT_PAGE;
+use super::sql::{
+    self, Expr, JoinKind, Literal, OrderingTerm, ResultColumn, Select, Statement, TableRef,
+};
+use super::{Database, Record, RecordValue, TextEncoding};
+
+/// The schema table answers to either name.
+const SCHEMA_TABLE_NAMES: &[&str] = &["sqlite_schema", "sqlite_master"];
+/// Names that mean the rowid unless a real column has taken them.
+const ROWID_NAMES: &[&str] = &["rowid", "_rowid_", "oid"];
+/// Column position standing for the rowid of a table without an INTEGER
+/// PRIMARY KEY to call it by.
+const ROWID_POSITION: usize = usize::MAX;
+
+/// One result row, its values in select-list order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    values: Vec<RecordValue>,
+}
+
+impl Row {
+    fn new(values: Vec<RecordValue>) -> Self {
+        Self { values }
+    }
+
+    /// The value of result column `index`, if there is such a column.
+    pub fn get(&self, index: usize) -> Option<&RecordValue> {
+        self.values.get(index)
+    }
+
+    pub fn values(&self) -> &[RecordValue] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<RecordValue> {
+        self.values
+    }
+}
+
+/// The result of `Database::query`: the column names, then the rows in
+/// order.
+///
+/// Rows are worked out as they are asked for, so a query with a LIMIT, or
+/// one that is dropped early, reads no further than it has to, and an
+/// error turns up at the row where it happened. Iteration ends after the
+/// first error. Queries that sort without an index to follow, or group,
+/// run their whole scan when the first row is asked for.
+pub struct Rows<'db> {
+    columns: Vec<String>,
+    rows: Box<dyn Iterator<Item = Result<Row>> + 'db>,
+}
+
+impl<'db> Rows<'db> {
+    fn new(columns: Vec<String>, rows: impl Iterator<Item = Result<Row>> + 'db) -> Self {
+        Self {
+            columns,
+            rows: Box::new(rows),
+        }
+    }
+
+    /// Result column names: the alias when there is one, else the column
+    /// name for plain column references, else the expression as written.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+}
+
+impl fmt::Debug for Rows<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rows")
+            .field("columns", &self.columns)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Iterator for Rows<'_> {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.rows.next();
+        if matches!(row, Some(Err(_))) {
+            self.rows = Box::new(iter::empty());
+        }
+        row
+    }
+}
+
+impl Database {
+    /// Run one SQL statement and return its rows.
+    ///
+    /// ```no_run
+    /// # fn main() -> codecrafters_sqlite::Result<()> {
+    /// let db = codecrafters_sqlite::Database::load("sample.db")?;
+    /// for row in db.query("SELECT name, color FROM apples WHERE id < 3")? {
+    ///     println!("{:?}", row?.values());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn query(&self, sql: &str) -> Result<Rows<'_>> {
+        match sql::parse(sql)? {
+            Statement::Select(select) => execute_select(self, &select),
+            Statement::CreateTable(_) | Statement::CreateIndex(_) => {
+                Err(QueryError::ReadOnly.into())
+            }
+        }
+    }
+}
+
+// Pick `column = literal` (either way round) out of a WHERE expression, as
+// (table qualifier, column name, literal).
+fn where_equality(expr: &Expr) -> Option<(Option<&str>, &str, &Literal)> {
+    match expr {
+        Expr::Binary {
+            left,
+            op: sql::BinaryOp::Eq,
+            right,
+        } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Column { table, name }, Expr::Literal(literal))
+            | (Expr::Literal(literal), Expr::Column { table, name }) => {
+                Some((table.as_deref(), name, literal))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The rowids of the first table left by `rowid > 5`, `rowid <= 10`,
+// `rowid BETWEEN 1 AND 9`, … terms in the top-level AND chain of a WHERE
+// clause (with the INTEGER PRIMARY KEY as good as `rowid`), or `None` when
+// no term bounds them. Like `index_terms`, this only narrows the scan; the
+// whole condition is still checked row by row.
+fn rowid_range(tables: &[TableSource], expr: &Expr) -> Option<RangeInclusive<i64>> {
+    use sql::BinaryOp::{Gt, GtEq, Lt, LtEq};
+
+    let is_rowid = |expr: &Expr| match expr {
+        Expr::Column { table, name } => matches!(
+            resolve_column(tables, table.as_deref(), name),
+            Ok((0, position)) if tables[0].is_rowid(position)
+        ),
+        _ => false,
+    };
+    let (mut start, mut end) = (i64::MIN, i64::MAX);
+    let mut bounded = false;
+    let mut bound = |op: sql::BinaryOp, literal: &Literal| {
+        // Round fractional bounds inwards: `> 5.5` is `>= 6`, `<= 5.5` is
+        // `<= 5`. Text and blobs compare above every number and NULL
+        // matches nothing; those are left to the row-by-row check.
+        let (floor, ceil) = match Affinity::Integer.apply(eval::literal_value(literal)) {
+            RecordValue::Int(n) => (n, n),
+            RecordValue::Real(f) if f.abs() < i64::MAX as f64 => {
+                (f.floor() as i64, f.ceil() as i64)
+            }
+            _ => return,
+        };
+        match op {
+            Gt => start = start.max(floor.saturating_add(1)),
+            GtEq => start = start.max(ceil),
+            Lt => end = end.min(ceil.saturating_sub(1)),
+            LtEq => end = end.min(floor),
+            _ => return,
+        }
+        bounded = true;
+    };
+
+    let mut terms = Vec::new();
+    conjuncts(expr, &mut terms);
+    for term in terms {
+        match term {
+            Expr::Binary { left, op, right } => match (left.as_ref(), right.as_ref()) {
+                (column, Expr::Literal(literal)) if is_rowid(column) => bound(*op, literal),
+                (Expr::Literal(literal), column) if is_rowid(column) => {
+                    let flipped = match op {
+                        Gt => Lt,
+                        GtEq => LtEq,
+                        Lt => Gt,
+                        LtEq => GtEq,
+                        other => *other,
+                    };
+                    bound(flipped, literal)
+                }
+                _ => {}
+            },
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated: false,
+            } if is_rowid(expr) => {
+                if let (Expr::Literal(low), Expr::Literal(high)) = (low.as_ref(), high.as_ref()) {
+                    bound(GtEq, low);
+                    bound(LtEq, high);
+                }
+            }
+            _ => {}
+        }
+    }
+    bounded.then_some(start..=end)
+}
+
+// Split a condition into the terms of its top-level AND chain.
+fn conjuncts<'a>(expr: &'a Expr, terms: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::Binary {
+            left,
+            op: sql::BinaryOp::And,
+            right,
+        } => {
+            conjuncts(left, terms);
+            conjuncts(right, terms);
+        }
+        _ => terms.push(expr),
+    }
+}
+
+// Collect the `column = literal` terms from the top-level AND chain of a
+// WHERE clause. Other terms are ignored here; they are still checked row by
+// row, the index only narrows down which rows get looked at.
+fn index_terms<'a>(expr: &'a Expr, terms: &mut Vec<(Option<&'a str>, &'a str, &'a Literal)>) {
+    let mut all = Vec::new();
+    conjuncts(expr, &mut all);
+    terms.extend(all.into_iter().filter_map(where_equality));
+}
+
+// A table named in FROM: where its rows live and what its columns are.
+struct TableSource {
+    name: String,
+    alias: Option<String>,
+    root: usize,
+    columns: Vec<(String, Affinity)>,
+    /// The INTEGER PRIMARY KEY column, which is stored as the rowid.
+    rowid_column: Option<usize>,
+}
+
+impl TableSource {
+    // Find `table_ref` in the schema.
+    fn load(db: &Database, table_ref: &TableRef) -> Result<Self> {
+        let table_name = table_ref.name.as_str();
+        if SCHEMA_TABLE_NAMES
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(table_name))
+        {
+            return Ok(Self::schema_table(table_ref));
+        }
+
+        let Some(table) = db.schema()?.table(table_name) else {
+            return Err(QueryError::NoSuchTable(table_name.to_string()).into());
+        };
+        if let Some(err) = table.error() {
+            return Err(err);
+        }
+        // Their rows live in an index B-tree keyed on the primary key.
+        if table.without_rowid {
+            let reason = "WITHOUT ROWID tables are not supported".to_string();
+            return Err(QueryError::Unsupported(reason).into());
+        }
+        Ok(Self {
+            name: table_name.to_string(),
+            alias: table_ref.alias.clone(),
+            root: table.root_page,
+            columns: table
+                .columns
+                .iter()
+                .map(|column| (column.name.clone(), column.affinity))
+                .collect(),
+            rowid_column: table.rowid_alias(),
+        })
+    }
+
+    // sqlite_schema itself: always rooted at page 1, with a fixed layout
+    // instead of a CREATE TABLE statement.
+    fn schema_table(table_ref: &TableRef) -> Self {
+        let column = |name: &str, affinity| (name.to_string(), affinity);
+        Self {
+            name: table_ref.name.clone(),
+            alias: table_ref.alias.clone(),
+            root: SCHEMA_ROOT_PAGE,
+            columns: vec![
+                column("type", Affinity::Text),
+                column("name", Affinity::Text),
+                column("tbl_name", Affinity::Text),
+                column("rootpage", Affinity::Integer),
+                column("sql", Affinity::Text),
+            ],
+            rowid_column: None,
+        }
+    }
+
+    // The name `table.column` references use: the alias if there is one.
+    fn qualifier(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    // Where column `name` sits in the table's records. `rowid`, `_rowid_`
+    // and `oid` are the INTEGER PRIMARY KEY if there is one, otherwise
+    // `ROWID_POSITION`.
+    fn position(&self, name: &str) -> Option<usize> {
+        let column = self
+            .columns
+            .iter()
+            .position(|(column, _)| column.eq_ignore_ascii_case(name));
+        if column.is_some() {
+            return column;
+        }
+        ROWID_NAMES
+            .iter()
+            .any(|rowid| rowid.eq_ignore_ascii_case(name))
+            .then_some(self.rowid_column.unwrap_or(ROWID_POSITION))
+    }
+
+    fn is_rowid(&self, position: usize) -> bool {
+        position == ROWID_POSITION || self.rowid_column == Some(position)
+    }
+
+    // The name a result column reading `position` gets.
+    fn column_name(&self, position: usize) -> &str {
+        match position {
+            ROWID_POSITION => "rowid",
+            _ => &self.columns[position].0,
+        }
+    }
+
+    fn affinity(&self, position: usize) -> Affinity {
+        match position {
+            ROWID_POSITION => Affinity::Integer,
+            _ => self.columns[position].1,
+        }
+    }
+
+    // Value of column `position` in one of this table's records.
+    fn value(&self, record: &Record, position: usize) -> RecordValue {
+        if self.is_rowid(position) {
+            // The INTEGER PRIMARY KEY is stored as NULL; its value is the rowid.
+            return RecordValue::Int(record.id);
+        }
+        // Rows written before an ALTER TABLE ADD COLUMN are shorter.
+        match record.values.get(position) {
+            // REAL columns store whole numbers as integers on disk.
+            Some(RecordValue::Int(n)) if self.columns[position].1 == Affinity::Real => {
+                RecordValue::Real(*n as f64)
+            }
+            Some(value) => value.clone(),
+            None => RecordValue::Null,
+        }
+    }
+}
+
+// Work out which table (and which of its columns) a column reference
+// means: `t.x` looks only at `t`, a bare `x` must exist in exactly one table.
+fn resolve_column(
+    tables: &[TableSource],
+    table: Option<&str>,
+    name: &str,
+) -> Result<(usize, usize)> {
+    let mut found = None;
+    for (index, source) in tables.iter().enumerate() {
+        if table.is_some_and(|table| !source.qualifier().eq_ignore_ascii_case(table)) {
+            continue;
+        }
+        if let Some(position) = source.position(name) {
+            if found.is_some() {
+                return Err(QueryError::AmbiguousColumn(name.to_string()).into());
+            }
+            found = Some((index, position));
+        }
+    }
+    match (found, table) {
+        (Some(found), _) => Ok(found),
+        (None, Some(table)) => Err(QueryError::NoSuchColumn(format!("{}.{}", table, name)).into()),
+        (None, None) => Err(QueryError::NoSuchColumn(name.to_string()).into()),
+    }
+}
+
+// The current row of every table in FROM, as seen by the expression
+// evaluator. A LEFT JOIN without a match leaves its table's record as
+// `None`, so all of its columns read as NULL.
+struct JoinedRow<'a> {
+    tables: &'a [TableSource],
+    records: &'a [Option<Record>],
+    encoding: TextEncoding,
+}
+
+impl Scope for JoinedRow<'_> {
+    fn column(&self, table: Option<&str>, name: &str) -> Result<RecordValue> {
+        let (index, position) = resolve_column(self.tables, table, name)?;
+        Ok(match self.records.get(index) {
+            Some(Some(record)) => self.tables[index].value(record, position),
+            _ => RecordValue::Null,
+        })
+    }
+
+    fn affinity(&self, table: Option<&str>, name: &str) -> Option<Affinity> {
+        let (index, position) = resolve_column(self.tables, table, name).ok()?;
+        Some(self.tables[index].affinity(position))
+    }
+
+    fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+}
+
+// The GROUP BY values of a row, ordered the way SQLite sorts them in the
+// database's encoding. NULLs are equal to each other here, so they form
+// one group of their own.
+struct GroupKey(Vec<RecordValue>, TextEncoding);
+
+impl Ord for GroupKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| a.sqlite_cmp_in(b, self.1))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for GroupKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for GroupKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for GroupKey {}
+
+// One GROUP BY bucket: the running aggregates, and the row that bare
+// (non-aggregated) columns are read from.
+struct Group {
+    accumulators: Vec<Accumulator>,
+    records: Option<Vec<Option<Record>>>,
+}
+
+impl Group {
+    fn new(aggregates: &[Expr]) -> Result<Self> {
+        Ok(Self {
+            accumulators: aggregates
+                .iter()
+                .map(Accumulator::new)
+                .collect::<Result<_>>()?,
+            records: None,
+        })
+    }
+}
+
+// Fold one row into its GROUP BY bucket, creating the bucket on first
+// sight. Buckets are kept sorted by key, which is also the order SQLite
+// reports groups in when there is no ORDER BY.
+fn add_to_group(
+    groups: &mut BTreeMap<GroupKey, Group>,
+    group_by: &[Expr],
+    aggregates: &[Expr],
+    row: &JoinedRow,
+) -> Result<()> {
+    let key = GroupKey(evaluate_all(group_by, row)?, row.encoding);
+    let group = match groups.entry(key) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(Group::new(aggregates)?),
+    };
+
+    // With exactly one min()/max(), bare columns come from the row it
+    // picked; otherwise from the last row of the group.
+    let picking = group
+        .accumulators
+        .iter()
+        .filter(|a| a.selects_row())
+        .count()
+        == 1;
+    let mut picked = false;
+    for accumulator in &mut group.accumulators {
+        picked |= accumulator.step(row)? && accumulator.selects_row();
+    }
+    if !picking || picked || group.records.is_none() {
+        group.records = Some(row.records.to_vec());
+    }
+    Ok(())
+}
+
+// A finished group as seen by the expression evaluator: aggregate calls read
+// their folded value, bare columns read the group's chosen row (NULL when
+// the group is the empty table).
+struct GroupRow<'a> {
+    tables: &'a [TableSource],
+    records: &'a [Option<Record>],
+    encoding: TextEncoding,
+    aggregates: &'a [Expr],
+    values: &'a [RecordValue],
+}
+
+impl Scope for GroupRow<'_> {
+    fn column(&self, table: Option<&str>, name: &str) -> Result<RecordValue> {
+        self.joined().column(table, name)
+    }
+
+    fn affinity(&self, table: Option<&str>, name: &str) -> Option<Affinity> {
+        self.joined().affinity(table, name)
+    }
+
+    fn aggregate(&self, call: &Expr) -> Option<RecordValue> {
+        self.aggregates
+            .iter()
+            .position(|aggregate| aggregate == call)
+            .map(|position| self.values[position].clone())
+    }
+
+    fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+}
+
+impl GroupRow<'_> {
+    fn joined(&self) -> JoinedRow<'_> {
+        JoinedRow {
+            tables: self.tables,
+            records: self.records,
+            encoding: self.encoding,
+        }
+    }
+}
+
+// How to find the rows of a joined table that go with the current rows of
+// the tables before it.
+enum JoinAccess {
+    // `t.pk = expr` on the INTEGER PRIMARY KEY: one B-tree seek per row.
+    Rowid(Expr),
+    // `t.col = expr` on the first column of an index: an index lookup.
+    Index {
+        root: usize,
+        affinity: Affinity,
+        key: Expr,
+    },
+    // Nothing to go on: scan the whole table for every outer row.
+    Scan,
+}
+
+// One table after the first in FROM, with how to join it.
+struct JoinStep {
+    left: bool,
+    constraint: Option<Expr>,
+    access: JoinAccess,
+}
+
+// Can `expr` be evaluated from the tables before `limit` alone?
+fn depends_only_on(expr: &Expr, tables: &[TableSource], limit: usize) -> bool {
+    match expr {
+        Expr::Literal(_) => true,
+        Expr::Column { table, name } => {
+            matches!(resolve_column(tables, table.as_deref(), name), Ok((index, _)) if index < limit)
+        }
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => {
+            depends_only_on(expr, tables, limit)
+        }
+        Expr::Binary { left, right, .. } => {
+            depends_only_on(left, tables, limit) && depends_only_on(right, tables, limit)
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => [expr, low, high]
+            .iter()
+            .all(|expr| depends_only_on(expr, tables, limit)),
+        Expr::Function { .. } => false,
+    }
+}
+
+// Pick the cheapest way to reach the rows of `tables[position]`: look for
+// `column = expr` terms whose other side only needs the earlier tables,
+// preferring the rowid over an index over a full scan.
+fn plan_join_access(
+    db: &Database,
+    tables: &[TableSource],
+    position: usize,
+    terms: &[&Expr],
+) -> Result<JoinAccess> {
+    let table = &tables[position];
+    let mut candidates = Vec::new();
+    for term in terms {
+        let Expr::Binary {
+            left,
+            op: sql::BinaryOp::Eq,
+            right,
+        } = term
+        else {
+            continue;
+        };
+        for (column, other) in [(left, right), (right, left)] {
+            let Expr::Column {
+                table: qualifier,
+                name,
+            } = column.as_ref()
+            else {
+                continue;
+            };
+            match resolve_column(tables, qualifier.as_deref(), name) {
+                Ok((index, column))
+                    if index == position && depends_only_on(other, tables, position) =>
+                {
+                    candidates.push((column, other.as_ref().clone()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some((_, key)) = candidates
+        .iter()
+        .find(|(column, _)| table.is_rowid(*column))
+    {
+        return Ok(JoinAccess::Rowid(key.clone()));
+    }
+    let indexes = usable_indexes(db, &table.name)?;
+    for (column, key) in candidates {
+        let name = table.column_name(column);
+        if let Some(index) = indexes
+            .iter()
+            .find(|index| index.columns[0].eq_ignore_ascii_case(name))
+        {
+            return Ok(JoinAccess::Index {
+                root: index.root,
+                affinity: table.affinity(column),
+                key,
+            });
+        }
+    }
+    Ok(JoinAccess::Scan)
+}
+
+// Plan every joined table: its ON terms (and, for inner joins, the WHERE
+// terms) may let it be reached by rowid or index instead of a scan.
+fn plan_joins(db: &Database, select: &Select, tables: &[TableSource]) -> Result<Vec<JoinStep>> {
+    let mut where_terms = Vec::new();
+    if let Some(expr) = &select.where_clause {
+        conjuncts(expr, &mut where_terms);
+    }
+    let mut steps = Vec::new();
+    for (position, join) in select.joins.iter().enumerate() {
+        let left = join.kind == JoinKind::Left;
+        let mut terms = Vec::new();
+        if let Some(constraint) = &join.constraint {
+            conjuncts(constraint, &mut terms);
+        }
+        if !left {
+            terms.extend(where_terms.iter().copied());
+        }
+        steps.push(JoinStep {
+            left,
+            constraint: join.constraint.clone(),
+            access: plan_join_access(db, tables, position + 1, &terms)?,
+        });
+    }
+    Ok(steps)
+}
+
+// Where rows come from: a table cursor, a rowid range, or rowids fetched
+// one at a time.
+type Candidates<'db> = Box<dyn Iterator<Item = Result<Record>> + 'db>;
+
+// Rowids to fetch rows by: one from a seek, or a walk over an index.
+type Rowids<'db> = Box<dyn Iterator<Item = Result<i64>> + 'db>;
+
+// The records of table `root` with the given rowids, in that order, skipping
+// rowids that have no row.
+fn fetch_rows<'db>(db: &'db Database, root: usize, rowids: Rowids<'db>) -> Candidates<'db> {
+    Box::new(rowids.filter_map(move |rowid| match rowid {
+        Ok(rowid) => db.fetch_record_by_rowid(root, rowid).transpose(),
+        Err(err) => Some(Err(err)),
+    }))
+}
+
+// How far the walk over one joined table's candidate rows has got, and
+// whether any of them matched the rows of the tables before it.
+struct Level<'db> {
+    candidates: Candidates<'db>,
+    matched: bool,
+}
+
+// Nested-loop join, one combination at a time: for the current rows of the
+// tables so far (`records`), walk the matching rows of the next table
+// (`levels` keeps each walk's place) until every table has a row. Without
+// joins this is just the rows of the first table.
+struct JoinedRows<'db> {
+    db: &'db Database,
+    tables: Vec<TableSource>,
+    steps: Vec<JoinStep>,
+    encoding: TextEncoding,
+    first: Candidates<'db>,
+    levels: Vec<Level<'db>>,
+    records: Vec<Option<Record>>,
+}
+
+impl<'db> JoinedRows<'db> {
+    // Move to the next combination, leaving one record per table in
+    // `records`; `false` once there are no more.
+    fn advance(&mut self) -> Result<bool> {
+        // Carry on from the last table's previous row.
+        if self.records.len() == self.tables.len() {
+            self.records.pop();
+        }
+        loop {
+            let depth = self.records.len();
+            if depth == self.tables.len() {
+                return Ok(true);
+            }
+            if depth == 0 {
+                match self.first.next().transpose()? {
+                    Some(record) => self.records.push(Some(record)),
+                    None => return Ok(false),
+                }
+                continue;
+            }
+
+            let step = &self.steps[depth - 1];
+            if self.levels.len() < depth {
+                let candidates = self.candidates(&step.access, &self.tables[depth])?;
+                self.levels.push(Level {
+                    candidates,
+                    matched: false,
+                });
+            }
+            let level = &mut self.levels[depth - 1];
+            match level.candidates.next().transpose()? {
+                Some(record) => {
+                    self.records.push(Some(record));
+                    let matches = match &step.constraint {
+                        Some(condition) => eval::is_true(condition, &self.scope())?,
+                        None => true,
+                    };
+                    if matches {
+                        self.levels[depth - 1].matched = true;
+                    } else {
+                        self.records.pop();
+                    }
+                }
+                // LEFT JOIN: no match still yields the left row, with this
+                // table NULL.
+                None if step.left && !level.matched => {
+                    level.matched = true;
+                    self.records.push(None);
+                }
+                None => {
+                    self.levels.pop();
+                    self.records.pop();
+                }
+            }
+        }
+    }
+
+    // The rows of `table` that may go with the current rows of the tables
+    // before it.
+    fn candidates(&self, access: &JoinAccess, table: &TableSource) -> Result<Candidates<'db>> {
+        let scope = self.scope();
+        let rowids: Rowids = match access {
+            JoinAccess::Scan => return Ok(Box::new(self.db.table_cursor(table.root))),
+            JoinAccess::Rowid(key) => match Affinity::Integer.apply(eval::evaluate(key, &scope)?) {
+                RecordValue::Int(rowid) => Box::new(iter::once(Ok(rowid))),
+                RecordValue::Real(f) if f.fract() == 0.0 => Box::new(iter::once(Ok(f as i64))),
+                _ => Box::new(iter::empty()),
+            },
+            JoinAccess::Index {
+                root,
+                affinity,
+                key,
+            } => match affinity.apply(eval::evaluate(key, &scope)?) {
+                // `= NULL` never matches.
+                RecordValue::Null => Box::new(iter::empty()),
+                value => Box::new(self.db.index_cursor(*root, vec![value])),
+            },
+        };
+        Ok(fetch_rows(self.db, table.root, rowids))
+    }
+
+    // The current combination, as seen by the expression evaluator.
+    fn scope(&self) -> JoinedRow<'_> {
+        JoinedRow {
+            tables: &self.tables,
+            records: &self.records,
+            encoding: self.encoding,
+        }
+    }
+}
+
+// An index we can walk in plain ascending order: its root page and the
+// leading columns whose order the B-tree follows.
+struct UsableIndex {
+    root: usize,
+    columns: Vec<String>,
+}
+
+// The indexes on `table_name` that lookups can use. Partial indexes are
+// skipped; DESC columns and non-default collations don't follow the plain
+// ascending order the lookups rely on, so the column list is cut off at the
+// first one.
+fn usable_indexes(db: &Database, table_name: &str) -> Result<Vec<UsableIndex>> {
+    let indexes = db
+        .schema()?
+        .indexes_on(table_name)
+        .filter(|index| !index.partial)
+        .filter_map(|index| {
+            let columns: Vec<String> = index
+                .columns
+                .iter()
+                .take_while(|column| {
+                    let binary = column
+                        .collation
+                        .as_deref()
+                        .map_or(true, |c| c.eq_ignore_ascii_case("BINARY"));
+                    !column.descending && binary
+                })
+                .map(|column| column.name.clone())
+                .collect();
+            (!columns.is_empty()).then_some(UsableIndex {
+                root: index.root_page,
+                columns,
+            })
+        })
+        .collect();
+    Ok(indexes)
+}
+
+// Pick the index whose leading columns are pinned down by the most
+// `column = value` terms, and build the key prefix to seek it with.
+//
+//   index (state, city, zip) + WHERE city = 'Oakland' AND state = 'CA'
+//   → prefix ['CA', 'Oakland']
+fn choose_index<'a>(
+    indexes: &'a [UsableIndex],
+    terms: &[(&str, RecordValue)],
+) -> Option<(&'a UsableIndex, Vec<RecordValue>)> {
+    let mut best: Option<(&UsableIndex, Vec<RecordValue>)> = None;
+
+    for index in indexes {
+        let prefix: Vec<RecordValue> = index
+            .columns
+            .iter()
+            .map_while(|column| {
+                terms
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(column))
+                    .map(|(_, value)| value.clone())
+            })
+            .collect();
+
+        let longer = best.as_ref().map_or(true, |(_, b)| prefix.len() > b.len());
+        if !prefix.is_empty() && longer {
+            best = Some((index, prefix));
+        }
+    }
+
+    best
+}
+
+// Does walking `index` with `prefix_len` columns pinned already yield rows
+// in ORDER BY order? Pinned columns are constant, so they may appear
+// anywhere in ORDER BY; the others must follow the index column order.
+//
+//   index (state, city), WHERE state = 'CA'  →  ORDER BY city      ✔
+//                                               ORDER BY state, city ✔
+//                                               ORDER BY city DESC ✘
+fn index_provides_order(index: &UsableIndex, prefix_len: usize, order_by: &[OrderingTerm]) -> bool {
+    let pinned = &index.columns[..prefix_len];
+    let mut next = prefix_len;
+
+    for term in order_by {
+        let Expr::Column { name, .. } = &term.expr else {
+            return false;
+        };
+        // Index order is ascending with NULLs (the smallest value) first.
+        if term.descending || !term.nulls_first() {
+            return false;
+        }
+        if pinned
+            .iter()
+            .any(|column| column.eq_ignore_ascii_case(name))
+        {
+            continue;
+        }
+        match index.columns.get(next) {
+            Some(column) if column.eq_ignore_ascii_case(name) => next += 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+// A table scan returns rows by ascending rowid, which is all `ORDER BY pk`
+// asks for when `pk` is the rowid or the INTEGER PRIMARY KEY.
+fn rowid_provides_order(order_by: &[OrderingTerm], table: &TableSource) -> bool {
+    match order_by.first() {
+        Some(OrderingTerm {
+            expr: Expr::Column { name, .. },
+            descending: false,
+            ..
+        }) => table
+            .position(name)
+            .is_some_and(|position| table.is_rowid(position)),
+        _ => false,
+    }
+}
+
+// Compare two rows' ORDER BY keys term by term, text in the order of
+// `encoding`. NULL placement follows NULLS FIRST/LAST and is not flipped by
+// DESC.
+fn compare_sort_keys(
+    a: &[RecordValue],
+    b: &[RecordValue],
+    order_by: &[OrderingTerm],
+    encoding: TextEncoding,
+) -> Ordering {
+    for ((x, y), term) in a.iter().zip(b).zip(order_by) {
+        let ordering = match (x, y) {
+            (RecordValue::Null, RecordValue::Null) => Ordering::Equal,
+            (RecordValue::Null, _) if term.nulls_first() => Ordering::Less,
+            (RecordValue::Null, _) => Ordering::Greater,
+            (_, RecordValue::Null) if term.nulls_first() => Ordering::Greater,
+            (_, RecordValue::Null) => Ordering::Less,
+            _ if term.descending => y.sqlite_cmp_in(x, encoding),
+            _ => x.sqlite_cmp_in(y, encoding),
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+// A running SELECT. Joined rows go through WHERE, then either straight out,
+// when they already come in ORDER BY order (so the scan stops once LIMIT is
+// met), or into the groups and the sort buffer, which the first call to
+// `next` fills from the whole scan.
+struct Selection<'db> {
+    rows: JoinedRows<'db>,
+    where_clause: Option<Expr>,
+    outputs: Vec<Expr>,
+    order_by: Vec<OrderingTerm>,
+    group_by: Vec<Expr>,
+    aggregates: Vec<Expr>,
+    having: Option<Expr>,
+    grouped: bool,
+    streaming: bool,
+    to_skip: usize,
+    remaining: Option<usize>,
+    buffered: Option<vec::IntoIter<Row>>,
+}
+
+impl Selection<'_> {
+    // Move to the next joined row that passes WHERE; `false` once there
+    // are none.
+    fn next_match(&mut self) -> Result<bool> {
+        while self.rows.advance()? {
+            match &self.where_clause {
+                Some(condition) if !eval::is_true(condition, &self.rows.scope())? => {}
+                _ => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+
+    // The next row in scan order, past OFFSET and up to LIMIT.
+    fn next_streamed(&mut self) -> Result<Option<Row>> {
+        if self.remaining == Some(0) {
+            return Ok(None);
+        }
+        while self.next_match()? {
+            if self.to_skip > 0 {
+                self.to_skip -= 1;
+                continue;
+            }
+            self.remaining = self.remaining.map(|remaining| remaining - 1);
+            let values = evaluate_all(&self.outputs, &self.rows.scope())?;
+            return Ok(Some(Row::new(values)));
+        }
+        Ok(None)
+    }
+
+    // Run the whole scan, fold it into groups if the query has them, and
+    // sort: the rows that are left once OFFSET and LIMIT are applied.
+    fn buffer(&mut self) -> Result<Vec<Row>> {
+        let encoding = self.rows.encoding;
+        let mut rows: Vec<(Vec<RecordValue>, Vec<RecordValue>)> = Vec::new();
+        let mut groups = BTreeMap::new();
+        while self.next_match()? {
+            let row = self.rows.scope();
+            if self.grouped {
+                add_to_group(&mut groups, &self.group_by, &self.aggregates, &row)?;
+            } else {
+                let sort_key = evaluate_sort_key(&self.order_by, &row)?;
+                rows.push((sort_key, evaluate_all(&self.outputs, &row)?));
+            }
+        }
+
+        if self.grouped {
+            // Aggregates over an empty table still produce one row, unless
+            // there are no groups to report because of GROUP BY.
+            if groups.is_empty() && self.group_by.is_empty() {
+                groups.insert(
+                    GroupKey(Vec::new(), encoding),
+                    Group::new(&self.aggregates)?,
+                );
+            }
+            for group in groups.values() {
+                let values = group
+                    .accumulators
+                    .iter()
+                    .map(Accumulator::finish)
+                    .collect::<Result<Vec<_>>>()?;
+                let scope = GroupRow {
+                    tables: &self.rows.tables,
+                    records: group.records.as_deref().unwrap_or(&[]),
+                    encoding,
+                    aggregates: &self.aggregates,
+                    values: &values,
+                };
+                if let Some(condition) = &self.having {
+                    if !eval::is_true(condition, &scope)? {
+                        continue;
+                    }
+                }
+                let sort_key = evaluate_sort_key(&self.order_by, &scope)?;
+                rows.push((sort_key, evaluate_all(&self.outputs, &scope)?));
+            }
+        }
+
+        // Stable, so ties keep their scan (or group) order.
+        rows.sort_by(|(a, _), (b, _)| compare_sort_keys(a, b, &self.order_by, encoding));
+        let rows = rows.into_iter().skip(self.to_skip);
+        let rows = rows.take(self.remaining.unwrap_or(usize::MAX));
+        Ok(rows.map(|(_, values)| Row::new(values)).collect())
+    }
+}
+
+impl Iterator for Selection<'_> {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.streaming {
+            return self.next_streamed().transpose();
+        }
+        if self.buffered.is_none() {
+            match self.buffer() {
+                Ok(rows) => self.buffered = Some(rows.into_iter()),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        self.buffered.as_mut()?.next().map(Ok)
+    }
+}
+
+// --------------------------------------------------------------------
+// execute_select() – run a parsed SELECT over one table or a join.
+// --------------------------------------------------------------------
+fn execute_select<'db>(db: &'db Database, select: &Select) -> Result<Rows<'db>> {
+    let Some(from) = &select.from else {
+        let reason = "SELECT without FROM is not supported".to_string();
+        return Err(QueryError::Unsupported(reason).into());
+    };
+    let mut tables = vec![TableSource::load(db, from)?];
+    for join in &select.joins {
+        tables.push(TableSource::load(db, &join.table)?);
+    }
+    let tables = tables;
+    let outer = &tables[0];
+    let encoding = db.header().text_encoding;
+
+    let column_exists = |name: &str| tables.iter().any(|table| table.position(name).is_some());
+
+    // The select list as expressions, and the name of each result column;
+    // `*` expands to every column of every table, `t.*` to the columns of
+    // `t`. Like SQLite, a column is named by its alias, else by the column
+    // it reads (as declared), else by the expression text.
+    let mut outputs: Vec<Expr> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    let add_columns_of = |table: &TableSource, outputs: &mut Vec<Expr>, names: &mut Vec<String>| {
+        for (name, _) in &table.columns {
+            outputs.push(Expr::Column {
+                table: Some(table.qualifier().to_string()),
+                name: name.clone(),
+            });
+            names.push(name.clone());
+        }
+    };
+    for column in &select.columns {
+        match column {
+            ResultColumn::Star => {
+                for table in &tables {
+                    add_columns_of(table, &mut outputs, &mut names);
+                }
+            }
+            ResultColumn::TableStar(qualifier) => {
+                match tables
+                    .iter()
+                    .find(|table| table.qualifier().eq_ignore_ascii_case(qualifier))
+                {
+                    Some(table) => add_columns_of(table, &mut outputs, &mut names),
+                    None => return Err(QueryError::NoSuchTable(qualifier.clone()).into()),
+                }
+            }
+            ResultColumn::Expr {
+                expr: Expr::Column { table, name },
+                alias,
+                ..
+            } => {
+                let (index, position) = resolve_column(&tables, table.as_deref(), name)?;
+                outputs.push(Expr::Column {
+                    table: table.clone(),
+                    name: name.clone(),
+                });
+                names.push(match alias {
+                    Some(alias) => alias.clone(),
+                    None => tables[index].column_name(position).to_string(),
+                });
+            }
+            ResultColumn::Expr { expr, alias, text } => {
+                outputs.push(expr.clone());
+                names.push(alias.clone().unwrap_or_else(|| text.clone()));
+            }
+        }
+    }
+
+    // Seek straight to the row when the WHERE clause pins down the first
+    // table's rowid, or use an index when one covers its `column = value`
+    // terms. The value gets the column's affinity first, like SQLite does
+    // before comparing. `= NULL` never matches, so there is nothing to
+    // look up in that case.
+    let mut terms = Vec::new();
+    if let Some(expr) = &select.where_clause {
+        index_terms(expr, &mut terms);
+    }
+    let mut rowid_key = None;
+    let mut lookup_terms: Vec<(&str, RecordValue)> = Vec::new();
+    for (table, column, literal) in terms {
+        if let Ok((0, position)) = resolve_column(&tables, table, column) {
+            let value = outer.affinity(position).apply(eval::literal_value(literal));
+            if outer.is_rowid(position) {
+                rowid_key.get_or_insert(value);
+            } else {
+                lookup_terms.push((column, value));
+            }
+        }
+    }
+    let indexes = usable_indexes(db, &outer.name)?;
+    let index = if lookup_terms
+        .iter()
+        .any(|(_, value)| matches!(value, RecordValue::Null))
+    {
+        None
+    } else {
+        choose_index(&indexes, &lookup_terms)
+    };
+
+    let steps = plan_joins(db, select, &tables)?;
+
+    // ORDER BY and GROUP BY may name a result column by position
+    // (`ORDER BY 2`) or by its alias (`SELECT name AS n ... ORDER BY n`);
+    // swap those for the expression they stand for before planning.
+    // ORDER BY prefers the alias, GROUP BY and HAVING a real column of
+    // that name.
+    let alias_expr = |name: &str| {
+        select.columns.iter().find_map(|column| match column {
+            ResultColumn::Expr {
+                expr,
+                alias: Some(alias),
+                ..
+            } if alias.eq_ignore_ascii_case(name) => Some(expr.clone()),
+            _ => None,
+        })
+    };
+    let result_reference = |expr: &Expr, clause: &'static str, alias_first: bool| {
+        let resolved = match expr {
+            Expr::Column { table: None, name } if alias_first || !column_exists(name) => {
+                alias_expr(name)
+            }
+            Expr::Literal(Literal::Integer(n)) => {
+                match outputs.get((*n as usize).wrapping_sub(1)) {
+                    Some(output) => Some(output.clone()),
+                    None => {
+                        return Err(QueryError::TermOutOfRange {
+                            clause,
+                            position: *n,
+                        }
+                        .into())
+                    }
+                }
+            }
+            _ => None,
+        };
+        Ok::<_, Error>(resolved.unwrap_or_else(|| expr.clone()))
+    };
+    let mut order_by = select.order_by.clone();
+    for term in &mut order_by {
+        term.expr = result_reference(&term.expr, "ORDER BY", true)?;
+    }
+    let group_by = select
+        .group_by
+        .iter()
+        .map(|expr| result_reference(expr, "GROUP BY", false))
+        .collect::<Result<Vec<_>>>()?;
+    let having = select.having.as_ref().map(|expr| {
+        expand_aliases(expr, &|name: &str| match column_exists(name) {
+            true => None,
+            false => alias_expr(name),
+        })
+    });
+
+    // Aggregate calls anywhere in the select list, HAVING or ORDER BY turn
+    // the query into one over groups (the whole table when there is no
+    // GROUP BY).
+    let mut aggregates = Vec::new();
+    for expr in outputs
+        .iter()
+        .chain(&having)
+        .chain(order_by.iter().map(|term| &term.expr))
+    {
+        aggregate::collect_aggregates(expr, &mut aggregates);
+    }
+    let grouped = !aggregates.is_empty() || !group_by.is_empty() || having.is_some();
+    // Group order has nothing to do with the order rows are scanned in.
+    let scan_order: &[OrderingTerm] = if grouped { &[] } else { &order_by };
+
+    // Decide how to fetch rows of the first table, and whether they already
+    // come out in ORDER BY order:
+    // 1. a rowid seek (at most one row, so trivially ordered),
+    // 2. an equality lookup on an index (ordered if the index agrees),
+    // 3. a plain table scan (ordered by rowid), cut down to the rowid range
+    //    the WHERE clause allows, if it limits the rowid,
+    // 4. a full walk of an index whose order matches ORDER BY.
+    // Joined rows come out in first-table order, but ORDER BY may name any
+    // table, so joins leave ordering to the sort at the end.
+    let joined = !steps.is_empty();
+    let scan_range = select
+        .where_clause
+        .as_ref()
+        .and_then(|expr| rowid_range(&tables, expr));
+    let (rowids, ordered): (Option<Rowids>, bool) = match (rowid_key, index) {
+        (Some(RecordValue::Int(rowid)), _) => (Some(Box::new(iter::once(Ok(rowid)))), true),
+        (Some(RecordValue::Real(f)), _) if f.fract() == 0.0 => {
+            (Some(Box::new(iter::once(Ok(f as i64)))), true)
+        }
+        // Text that doesn't look like an integer, a fraction or NULL.
+        (Some(_), _) => (Some(Box::new(iter::empty())), true),
+        (None, Some((index, prefix))) => {
+            let ordered = !joined && index_provides_order(index, prefix.len(), scan_order);
+            (Some(Box::new(db.index_cursor(index.root, prefix))), ordered)
+        }
+        (None, None) if scan_order.is_empty() => (None, true),
+        (None, None) if joined => (None, false),
+        (None, None) if rowid_provides_order(scan_order, outer) => (None, true),
+        // Sorting part of the table beats walking a whole index for order.
+        (None, None) if scan_range.is_some() => (None, false),
+        (None, None) => match indexes
+            .iter()
+            .find(|index| index_provides_order(index, 0, scan_order))
+        {
+            Some(index) => (
+                Some(Box::new(db.index_cursor(index.root, Vec::new()))),
+                true,
+            ),
+            None => (None, false),
+        },
+    };
+
+    let limit = match &select.limit {
+        Some(limit) => Some(limit_bounds(limit)?),
+        None => None,
+    };
+    let (offset, max_rows) = limit.unwrap_or((0, None));
+    if max_rows == Some(0) {
+        return Ok(Rows::new(names, iter::empty()));
+    }
+
+    // select count(*) from table: every leaf page knows how many cells it
+    // holds, so there is no need to decode the rows.
+    if let [Expr::Function {
+        name, star: true, ..
+    }] = outputs.as_slice()
+    {
+        let plain =
+            select.where_clause.is_none() && !joined && group_by.is_empty() && having.is_none();
+        if name.eq_ignore_ascii_case("count") && plain {
+            let root = outer.root;
+            let count = iter::once_with(move || {
+                let count = db.count_rows(root)? as i64;
+                Ok(Row::new(vec![RecordValue::Int(count)]))
+            });
+            return Ok(Rows::new(names, count.skip(offset)));
+        }
+    }
+
+    let first: Candidates = match (rowids, scan_range) {
+        (Some(rowids), _) => fetch_rows(db, outer.root, rowids),
+        (None, Some(range)) => Box::new(db.table_cursor(outer.root).range(range)),
+        (None, None) => Box::new(db.table_cursor(outer.root)),
+    };
+    let selection = Selection {
+        rows: JoinedRows {
+            db,
+            tables,
+            steps,
+            encoding,
+            first,
+            levels: Vec::new(),
+            records: Vec::new(),
+        },
+        where_clause: select.where_clause.clone(),
+        outputs,
+        order_by,
+        group_by,
+        aggregates,
+        having,
+        grouped,
+        streaming: ordered && !grouped,
+        to_skip: offset,
+        remaining: max_rows,
+        buffered: None,
+    };
+    Ok(Rows::new(names, selection))
+}
+
+// Rebuild `expr` with every column reference that `alias` knows replaced by
+// the aliased expression (HAVING may use result aliases anywhere inside).
+fn expand_aliases(expr: &Expr, alias: &dyn Fn(&str) -> Option<Expr>) -> Expr {
+    let expand = |expr: &Expr| Box::new(expand_aliases(expr, alias));
+    match expr {
+        Expr::Column { table: None, name } => alias(name).unwrap_or_else(|| expr.clone()),
+        Expr::Literal(_) | Expr::Column { .. } => expr.clone(),
+        Expr::Unary { op, expr } => Expr::Unary {
+            op: *op,
+            expr: expand(expr),
+        },
+        Expr::Binary { left, op, right } => Expr::Binary {
+            left: expand(left),
+            op: *op,
+            right: expand(right),
+        },
+        Expr::IsNull { expr, negated } => Expr::IsNull {
+            expr: expand(expr),
+            negated: *negated,
+        },
+        Expr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => Expr::Between {
+            expr: expand(expr),
+            low: expand(low),
+            high: expand(high),
+            negated: *negated,
+        },
+        Expr::Function { name, args, star } => Expr::Function {
+            name: name.clone(),
+            args: args.iter().map(|arg| expand_aliases(arg, alias)).collect(),
+            star: *star,
+        },
+    }
+}
+
+// Evaluate the ORDER BY terms for one row (or group).
+fn evaluate_sort_key(order_by: &[OrderingTerm], scope: &dyn Scope) -> Result<Vec<RecordValue>> {
+    order_by
+        .iter()
+        .map(|term| eval::evaluate(&term.expr, scope))
+        .collect()
+}
+
+// Evaluate each expression of the select list (or any list) for one row.
+fn evaluate_all(exprs: &[Expr], scope: &dyn Scope) -> Result<Vec<RecordValue>> {
+    exprs
+        .iter()
+        .map(|expr| eval::evaluate(expr, scope))
+        .collect()
+}
+
+// Evaluate `LIMIT count [OFFSET offset]` into (offset, max rows).
+// A negative count means "no limit", a negative offset counts as zero.
+fn limit_bounds(limit: &sql::Limit) -> Result<(usize, Option<usize>)> {
+    let integer = |expr: &Expr| -> Result<i64> {
+        match eval::evaluate(expr, &eval::NoColumns)? {
+            RecordValue::Int(n) => Ok(n),
+            other => Err(QueryError::DatatypeMismatch(format!(
+                "LIMIT/OFFSET must be an integer, got {:?}",
+                other
+            ))
+            .into()),
+        }
+    };
+    let count = integer(&limit.count)?;
+    let offset = match &limit.offset {
+        Some(expr) => integer(expr)?.max(0) as usize,
+        None => 0,
+    };
+    let max_rows = if count < 0 {
+        None
+    } else {
+        Some(count as usize)
+    };
+    Ok((offset, max_rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joins_db() -> Database {
+        Database::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/joins.db"
+        ))
+        .unwrap()
+    }
+
+    // How each joined table of `sql` would be reached, as "rowid", the
+    // index root page, or "scan".
+    fn join_plan(sql: &str) -> Vec<String> {
+        let db = joins_db();
+        let Ok(Statement::Select(select)) = sql::parse(sql) else {
+            panic!("not a SELECT: {}", sql);
+        };
+        let mut tables = vec![TableSource::load(&db, select.from.as_ref().unwrap()).unwrap()];
+        for join in &select.joins {
+            tables.push(TableSource::load(&db, &join.table).unwrap());
+        }
+        plan_joins(&db, &select, &tables)
+            .unwrap()
+            .iter()
+            .map(|step| match &step.access {
+                JoinAccess::Rowid(_) => "rowid".to_string(),
+                JoinAccess::Index { root, .. } => format!("index {}", root),
+                JoinAccess::Scan => "scan".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn joins_reach_tables_by_rowid_index_or_scan() {
+        let db = Database::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/joins.db"
+        ))
+        .unwrap();
+        let index = db.schema().unwrap().indexes_on("orders").next().unwrap();
+        let by_index = format!("index {}", index.root_page);
+
+        assert_eq!(
+            join_plan("SELECT * FROM orders o JOIN customers c ON c.id = o.customer_id"),
+            ["rowid"]
+        );
+        assert_eq!(
+            join_plan("SELECT * FROM customers c LEFT JOIN orders o ON o.customer_id = c.id"),
+            [by_index.as_str()]
+        );
+        // A comma join has no ON, but its WHERE terms serve the same purpose.
+        assert_eq!(
+            join_plan(
+                "SELECT * FROM customers c, orders o WHERE c.name = 'Ann' AND o.customer_id = c.id"
+            ),
+            [by_index.as_str()]
+        );
+        // WHERE terms don't restrict the right side of a LEFT JOIN.
+        assert_eq!(
+            join_plan("SELECT * FROM customers c LEFT JOIN orders o WHERE o.customer_id = c.id"),
+            ["scan"]
+        );
+        assert_eq!(
+            join_plan("SELECT * FROM orders o, customers c WHERE c.name = o.item"),
+            ["scan"]
+        );
+    }
+}
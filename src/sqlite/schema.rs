@@ -0,0 +1,559 @@
+//! # sqlite/schema.rs – what sqlite_schema says is in the file
+//!
+//! ```text
+//!  sqlite_schema (B-tree rooted at page 1)
+//!   type     name          tbl_name  rootpage  sql
+//!   table    apples        apples    2         CREATE TABLE apples (...)
+//!   index    idx_color     apples    4         CREATE INDEX idx_color ON ...
+//!   index    sqlite_auto…  apples    5         NULL
+//!        │
+//!        ▼ parse each CREATE statement
+//!  Schema { tables: [Table { columns: [Column …] }], indexes: [Index …], … }
+//! ```
+//!
+//! The schema is read once per `Database`, the first time it's needed,
+//! by walking the whole schema B-tree (it spills past page 1 once there
+//! are enough tables).
+//!
+//! Indexes SQLite creates for PRIMARY KEY and UNIQUE constraints have no
+//! SQL of their own; their columns come from the constraint that made
+//! them. `sqlite_autoindex_<table>_<N>` belongs to the N-th such
+//! constraint in the CREATE TABLE statement, skipping the INTEGER PRIMARY
+//! KEY (which is the rowid, not an index) and any that repeat the columns
+//! of an earlier one.
+//!
+use super::error::{Error, Result};
+use super::eval::Affinity;
+use super::sql::{self, ColumnDef, CreateTable, IndexedColumn, Statement, TableConstraint};
+use super::{Database, RecordValue};
+
+/// sqlite_schema is always rooted at page 1.
+pub(crate) const SCHEMA_ROOT_PAGE: usize = 1;
+
+/// Every table, index, view and trigger in the database.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    tables: Vec<Table>,
+    indexes: Vec<Index>,
+    views: Vec<View>,
+    triggers: Vec<Trigger>,
+}
+
+/// A table and its column definitions.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Table {
+    pub name: String,
+    /// Root page of the table's B-tree.
+    pub root_page: usize,
+    /// The CREATE TABLE statement as stored.
+    pub sql: String,
+    /// In declaration order, which is also the order values are stored in
+    /// each record. Empty for virtual tables.
+    pub columns: Vec<Column>,
+    /// Rows are kept in an index B-tree keyed on the primary key.
+    pub without_rowid: bool,
+    rowid_alias: Option<usize>,
+    error: Option<String>,
+}
+
+/// One column of a table, as declared.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Column {
+    pub name: String,
+    /// The type name as written, e.g. `VARCHAR(20)`; empty if omitted.
+    pub declared_type: String,
+    pub affinity: Affinity,
+    pub not_null: bool,
+    /// The DEFAULT value as written, e.g. `0` or `'none'`.
+    pub default: Option<String>,
+    /// Part of the primary key, declared on the column or the table.
+    pub primary_key: bool,
+    pub collation: Option<String>,
+}
+
+/// An index, written out or created for a PRIMARY KEY / UNIQUE constraint.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Index {
+    pub name: String,
+    /// The table it indexes.
+    pub table: String,
+    pub root_page: usize,
+    /// The CREATE INDEX statement; `None` for constraint indexes.
+    pub sql: Option<String>,
+    pub unique: bool,
+    /// Only covers rows matching a WHERE clause.
+    pub partial: bool,
+    /// Key columns, in order. Empty for indexes on expressions.
+    pub columns: Vec<IndexColumn>,
+}
+
+/// One key column of an index.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct IndexColumn {
+    pub name: String,
+    /// The collation the index is ordered by: the one named in the index,
+    /// else the column's own.
+    pub collation: Option<String>,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct View {
+    pub name: String,
+    pub sql: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Trigger {
+    pub name: String,
+    /// The table (or view) it fires on.
+    pub table: String,
+    pub sql: String,
+}
+
+impl Schema {
+    pub(crate) fn load(db: &Database) -> Result<Self> {
+        let mut schema = Self::default();
+        // Constraint indexes need their table's definition, which may come
+        // later in sqlite_schema.
+        let mut index_rows = Vec::new();
+        let mut constraint_keys = Vec::new();
+
+        for record in db.table_cursor(SCHEMA_ROOT_PAGE) {
+            let record = record?;
+            let text = |i: usize| match record.values.get(i) {
+                Some(RecordValue::Text(text)) => Some(text.clone()),
+                _ => None,
+            };
+            let (Some(typ), Some(name)) = (text(0), text(1)) else {
+                continue;
+            };
+            let table = text(2).unwrap_or_default();
+            let root_page = match record.values.get(3) {
+                Some(RecordValue::Int(page)) => *page as usize,
+                _ => 0,
+            };
+            let sql = text(4);
+
+            match typ.as_str() {
+                // A statement we can't read only costs that one table; the
+                // rest of the schema stays usable.
+                "table" => {
+                    let definition = match sql.as_deref().map(sql::parse) {
+                        Some(Ok(Statement::CreateTable(definition))) => Ok(definition),
+                        Some(Ok(_)) => Err("not a CREATE TABLE statement".to_string()),
+                        Some(Err(err)) => Err(err.to_string()),
+                        None => Err("missing CREATE TABLE".to_string()),
+                    };
+                    let sql = sql.unwrap_or_default();
+                    let table = match definition {
+                        Ok(definition) => {
+                            constraint_keys.push((name.clone(), unique_keys(&definition)));
+                            Table::new(name, root_page, sql, &definition)
+                        }
+                        Err(reason) => Table::unreadable(name, root_page, sql, reason),
+                    };
+                    schema.tables.push(table);
+                }
+                "index" => index_rows.push((name, table, root_page, sql)),
+                "view" => schema.views.push(View {
+                    name,
+                    sql: sql.unwrap_or_default(),
+                }),
+                "trigger" => schema.triggers.push(Trigger {
+                    name,
+                    table,
+                    sql: sql.unwrap_or_default(),
+                }),
+                _ => {}
+            }
+        }
+
+        for (name, table, root_page, sql) in index_rows {
+            let index = match sql {
+                Some(sql) => schema.declared_index(name, table, root_page, sql),
+                None => {
+                    let keys = constraint_keys
+                        .iter()
+                        .find(|(owner, _)| owner.eq_ignore_ascii_case(&table))
+                        .map_or(&[][..], |(_, keys)| keys.as_slice());
+                    constraint_index(name, table, root_page, keys)
+                }
+            };
+            schema.indexes.push(index);
+        }
+        Ok(schema)
+    }
+
+    fn declared_index(&self, name: String, table: String, root_page: usize, sql: String) -> Index {
+        // Indexes on expressions don't parse; they keep an empty column list.
+        let definition = match sql::parse(&sql) {
+            Ok(Statement::CreateIndex(definition)) => Some(definition),
+            _ => None,
+        };
+        let owner = self.table(&table);
+        let columns = definition.as_ref().map_or(Vec::new(), |definition| {
+            definition
+                .columns
+                .iter()
+                .map(|column| index_column(column, owner))
+                .collect()
+        });
+        Index {
+            name,
+            table,
+            root_page,
+            unique: definition.as_ref().is_some_and(|d| d.unique),
+            partial: definition
+                .as_ref()
+                .is_some_and(|d| d.where_clause.is_some()),
+            sql: Some(sql),
+            columns,
+        }
+    }
+
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    pub fn indexes(&self) -> &[Index] {
+        &self.indexes
+    }
+
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    /// Look a table up by name, ignoring ASCII case like SQLite does.
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables
+            .iter()
+            .find(|table| table.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Indexes on the table called `table`, in schema order.
+    pub fn indexes_on<'a>(&'a self, table: &'a str) -> impl Iterator<Item = &'a Index> + 'a {
+        self.indexes
+            .iter()
+            .filter(move |index| index.table.eq_ignore_ascii_case(table))
+    }
+}
+
+impl Table {
+    fn new(name: String, root_page: usize, sql: String, definition: &CreateTable) -> Self {
+        let key_columns: Vec<&str> = definition
+            .constraints
+            .iter()
+            .filter_map(|constraint| match constraint {
+                TableConstraint::PrimaryKey(columns) => Some(columns),
+                TableConstraint::Unique(_) => None,
+            })
+            .flatten()
+            .map(|column| column.name.as_str())
+            .collect();
+        let columns = definition
+            .columns
+            .iter()
+            .map(|def| Column {
+                name: def.name.clone(),
+                declared_type: def.declared_type.clone(),
+                affinity: Affinity::from_declared_type(&def.declared_type),
+                not_null: def.not_null,
+                default: def.default.clone(),
+                primary_key: def.primary_key.is_some()
+                    || key_columns
+                        .iter()
+                        .any(|k| k.eq_ignore_ascii_case(&def.name)),
+                collation: def.collation.clone(),
+            })
+            .collect();
+
+        Self {
+            name,
+            root_page,
+            sql,
+            columns,
+            without_rowid: definition.without_rowid,
+            rowid_alias: rowid_alias(definition),
+            error: None,
+        }
+    }
+
+    // A table whose CREATE TABLE statement doesn't parse: listed with its
+    // SQL, but without columns.
+    fn unreadable(name: String, root_page: usize, sql: String, reason: String) -> Self {
+        Self {
+            name,
+            root_page,
+            sql,
+            columns: Vec::new(),
+            without_rowid: false,
+            rowid_alias: None,
+            error: Some(reason),
+        }
+    }
+
+    /// Look a column up by name, ignoring ASCII case.
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns
+            .iter()
+            .find(|column| column.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Position of the INTEGER PRIMARY KEY column, whose value is the rowid
+    /// (the record stores NULL in its place).
+    pub fn rowid_alias(&self) -> Option<usize> {
+        self.rowid_alias
+    }
+
+    /// The error querying this table gives, if its CREATE TABLE statement
+    /// couldn't be read.
+    pub fn error(&self) -> Option<Error> {
+        self.error
+            .as_ref()
+            .map(|reason| malformed(&self.name, reason.as_str()))
+    }
+}
+
+/// The column that is another name for the rowid: the sole primary key
+/// column of a rowid table when its declared type is exactly `INTEGER`.
+/// A column-level `INTEGER PRIMARY KEY DESC` is the historical exception,
+/// and stays an ordinary column.
+fn rowid_alias(definition: &CreateTable) -> Option<usize> {
+    if definition.without_rowid {
+        return None;
+    }
+    let is_integer = |position: usize| {
+        definition.columns[position]
+            .declared_type
+            .eq_ignore_ascii_case("INTEGER")
+    };
+    for (position, column) in definition.columns.iter().enumerate() {
+        if let Some(key) = column.primary_key {
+            return (!key.descending && is_integer(position)).then_some(position);
+        }
+    }
+    for constraint in &definition.constraints {
+        if let TableConstraint::PrimaryKey(columns) = constraint {
+            let [column] = columns.as_slice() else {
+                return None;
+            };
+            return definition
+                .columns
+                .iter()
+                .position(|def| def.name.eq_ignore_ascii_case(&column.name))
+                .filter(|&position| is_integer(position));
+        }
+    }
+    None
+}
+
+/// Key columns of each index SQLite creates for the table's PRIMARY KEY
+/// and UNIQUE constraints, in the order it numbers them.
+fn unique_keys(definition: &CreateTable) -> Vec<Vec<IndexColumn>> {
+    let alias = rowid_alias(definition);
+    let column_key = |def: &ColumnDef, descending: bool| {
+        vec![IndexColumn {
+            name: def.name.clone(),
+            collation: def.collation.clone(),
+            descending,
+        }]
+    };
+
+    let mut keys = Vec::new();
+    for (position, def) in definition.columns.iter().enumerate() {
+        if let Some(key) = def.primary_key {
+            // WITHOUT ROWID tables are themselves keyed on the primary key.
+            if alias != Some(position) && !definition.without_rowid {
+                keys.push(column_key(def, key.descending));
+            }
+        }
+        if def.unique {
+            keys.push(column_key(def, false));
+        }
+    }
+    for constraint in &definition.constraints {
+        let columns = match constraint {
+            TableConstraint::PrimaryKey(_) if alias.is_some() || definition.without_rowid => {
+                continue
+            }
+            TableConstraint::PrimaryKey(columns) | TableConstraint::Unique(columns) => columns,
+        };
+        let owner = |name: &str| {
+            definition
+                .columns
+                .iter()
+                .find(|def| def.name.eq_ignore_ascii_case(name))
+        };
+        keys.push(
+            columns
+                .iter()
+                .map(|column| IndexColumn {
+                    name: column.name.clone(),
+                    collation: column
+                        .collation
+                        .clone()
+                        .or_else(|| owner(&column.name).and_then(|def| def.collation.clone())),
+                    descending: column.descending,
+                })
+                .collect(),
+        );
+    }
+
+    let mut distinct: Vec<Vec<IndexColumn>> = Vec::new();
+    for key in keys {
+        let repeats = distinct.iter().any(|earlier| {
+            earlier.len() == key.len()
+                && earlier
+                    .iter()
+                    .zip(&key)
+                    .all(|(a, b)| a.name.eq_ignore_ascii_case(&b.name))
+        });
+        if !repeats {
+            distinct.push(key);
+        }
+    }
+    distinct
+}
+
+fn constraint_index(
+    name: String,
+    table: String,
+    root_page: usize,
+    keys: &[Vec<IndexColumn>],
+) -> Index {
+    // sqlite_autoindex_<table>_<N>, N counting from 1.
+    let columns = name
+        .rsplit('_')
+        .next()
+        .and_then(|n| n.parse::<usize>().ok())
+        .and_then(|n| keys.get(n.checked_sub(1)?))
+        .cloned()
+        .unwrap_or_default();
+    Index {
+        name,
+        table,
+        root_page,
+        sql: None,
+        unique: true,
+        partial: false,
+        columns,
+    }
+}
+
+fn index_column(column: &IndexedColumn, table: Option<&Table>) -> IndexColumn {
+    let collation = column.collation.clone().or_else(|| {
+        table
+            .and_then(|table| table.column(&column.name))
+            .and_then(|column| column.collation.clone())
+    });
+    IndexColumn {
+        name: column.name.clone(),
+        collation,
+        descending: column.descending,
+    }
+}
+
+fn malformed(name: &str, reason: impl Into<String>) -> Error {
+    Error::MalformedSchema {
+        name: name.to_string(),
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(sql: &str) -> (Table, Vec<Vec<IndexColumn>>) {
+        let Ok(Statement::CreateTable(definition)) = sql::parse(sql) else {
+            panic!("not a CREATE TABLE: {}", sql);
+        };
+        let keys = unique_keys(&definition);
+        (
+            Table::new("t".to_string(), 2, sql.to_string(), &definition),
+            keys,
+        )
+    }
+
+    fn key_names(keys: &[Vec<IndexColumn>]) -> Vec<Vec<&str>> {
+        keys.iter()
+            .map(|key| key.iter().map(|column| column.name.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn reads_column_definitions() {
+        let (table, _) = table(
+            "CREATE TABLE \"order lines\" (\n\
+             \tid INTEGER PRIMARY KEY AUTOINCREMENT,\n\
+             \tprice DECIMAL(10, 2) NOT NULL DEFAULT 0.0 CHECK (price >= 0),\n\
+             \t[name] varchar(20) COLLATE NOCASE UNIQUE,\n\
+             \tnote,\n\
+             \tpart_id INT REFERENCES parts (id) ON DELETE CASCADE,\n\
+             \tCONSTRAINT positive CHECK (price > 0 AND (id > 0))\n\
+             )",
+        );
+        let names: Vec<&str> = table.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["id", "price", "name", "note", "part_id"]);
+
+        let price = table.column("PRICE").unwrap();
+        assert_eq!(price.declared_type, "DECIMAL(10, 2)");
+        assert_eq!(price.affinity, Affinity::Numeric);
+        assert!(price.not_null);
+        assert_eq!(price.default.as_deref(), Some("0.0"));
+
+        let name = table.column("name").unwrap();
+        assert_eq!(name.affinity, Affinity::Text);
+        assert_eq!(name.collation.as_deref(), Some("NOCASE"));
+        assert_eq!(table.column("note").unwrap().affinity, Affinity::Blob);
+        assert_eq!(table.rowid_alias(), Some(0));
+    }
+
+    #[test]
+    fn finds_the_rowid_alias() {
+        let alias = |sql: &str| table(sql).0.rowid_alias();
+        assert_eq!(
+            alias("CREATE TABLE t (a TEXT, b integer PRIMARY KEY)"),
+            Some(1)
+        );
+        assert_eq!(
+            alias("CREATE TABLE t (a, b INTEGER, PRIMARY KEY (b DESC))"),
+            Some(1)
+        );
+        assert_eq!(alias("CREATE TABLE t (a INT PRIMARY KEY)"), None);
+        assert_eq!(alias("CREATE TABLE t (a INTEGER PRIMARY KEY DESC)"), None);
+        assert_eq!(
+            alias("CREATE TABLE t (a INTEGER, b, PRIMARY KEY (a, b))"),
+            None
+        );
+        assert_eq!(
+            alias("CREATE TABLE t (a INTEGER PRIMARY KEY, b) WITHOUT ROWID"),
+            None
+        );
+    }
+
+    #[test]
+    fn numbers_constraint_indexes_in_declaration_order() {
+        let (t, keys) = table(
+            "CREATE TABLE t (a TEXT PRIMARY KEY, b UNIQUE, c, d, \
+             UNIQUE (c, d) UNIQUE (b))",
+        );
+        assert_eq!(key_names(&keys), [vec!["a"], vec!["b"], vec!["c", "d"]]);
+        assert!(t.columns[0].primary_key);
+        assert!(!t.columns[1].primary_key);
+
+        let (_, keys) = table("CREATE TABLE t (id INTEGER PRIMARY KEY, e UNIQUE)");
+        assert_eq!(key_names(&keys), [vec!["e"]]);
+    }
+}
@@ -0,0 +1,233 @@
+//! # sql/ast.rs – the typed shape of a parsed statement
+//!
+//! ```text
+//!  SELECT name FROM apples WHERE color = 'Red' ORDER BY name LIMIT 2
+//!
+//!  Select
+//!   ├─ columns:  [Expr(Column "name")]
+//!   ├─ from:     TableRef "apples"
+//!   ├─ where:    Binary(Column "color", Eq, Literal 'Red')
+//!   ├─ order_by: [name ASC]
+//!   └─ limit:    Limit { count: 2 }
+//! ```
+//!
+
+// One statement is parsed per query, so the size difference doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Select(Select),
+    CreateTable(CreateTable),
+    CreateIndex(CreateIndex),
+}
+
+/// `CREATE TABLE name (column_def, ... [, table_constraint, ...]) [options]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTable {
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    /// Table-level `PRIMARY KEY (...)` and `UNIQUE (...)`, in order.
+    /// CHECK and FOREIGN KEY constraints are skipped.
+    pub constraints: Vec<TableConstraint>,
+    pub without_rowid: bool,
+    pub strict: bool,
+    /// `CREATE VIRTUAL TABLE ... USING module`: the columns are up to the
+    /// module, so `columns` is empty.
+    pub module: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    /// The type name as written, e.g. `DECIMAL(10,2)`; empty if omitted.
+    pub declared_type: String,
+    pub not_null: bool,
+    /// The DEFAULT value as written: `0`, `'none'`, `(1 + 2)`, ...
+    pub default: Option<String>,
+    pub primary_key: Option<ColumnPrimaryKey>,
+    pub unique: bool,
+    pub collation: Option<String>,
+}
+
+/// A column-level `PRIMARY KEY [ASC|DESC] [AUTOINCREMENT]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnPrimaryKey {
+    pub descending: bool,
+    pub autoincrement: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableConstraint {
+    PrimaryKey(Vec<IndexedColumn>),
+    Unique(Vec<IndexedColumn>),
+}
+
+/// `CREATE [UNIQUE] INDEX name ON table (col [ASC|DESC], ...) [WHERE expr]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateIndex {
+    pub name: String,
+    pub table: String,
+    pub unique: bool,
+    pub columns: Vec<IndexedColumn>,
+    /// Partial indexes only cover rows matching this expression.
+    pub where_clause: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedColumn {
+    pub name: String,
+    pub collation: Option<String>,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub columns: Vec<ResultColumn>,
+    pub from: Option<TableRef>,
+    /// Further tables joined to `from`, in the order they appear.
+    pub joins: Vec<Join>,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultColumn {
+    /// `*`
+    Star,
+    /// `table.*`
+    TableStar(String),
+    /// `expr [AS alias]`; `text` is the expression as written, which
+    /// names the result column when there is no alias.
+    Expr {
+        expr: Expr,
+        alias: Option<String>,
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRef {
+    pub name: String,
+    pub alias: Option<String>,
+}
+
+/// `[LEFT] JOIN table [ON expr]`; a comma join is an inner join without
+/// a constraint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub table: TableRef,
+    pub constraint: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    /// Keeps left rows without a match, with the right table all NULL.
+    Left,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub descending: bool,
+    /// Explicit `NULLS FIRST` / `NULLS LAST`; by default NULLs sort as the
+    /// smallest value (first for ASC, last for DESC).
+    pub nulls: Option<NullsOrder>,
+}
+
+impl OrderingTerm {
+    pub fn nulls_first(&self) -> bool {
+        match self.nulls {
+            Some(NullsOrder::First) => true,
+            Some(NullsOrder::Last) => false,
+            None => !self.descending,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limit {
+    pub count: Expr,
+    pub offset: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Null,
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Blob(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Negate,
+    Plus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    /// `||`
+    Concat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Literal),
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    /// `expr IS NULL` / `expr IS NOT NULL`
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    /// `expr [NOT] BETWEEN low AND high`
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    /// `name(args)`; `count(*)` is stored with `star = true` and no args.
+    Function {
+        name: String,
+        args: Vec<Expr>,
+        star: bool,
+    },
+}
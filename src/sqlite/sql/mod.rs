@@ -0,0 +1,47 @@
+//! # sqlite/sql – from SQL text to a typed AST
+//!
+//! ```text
+//!  &str ──Lexer──▶ Vec<Token> ──Parser──▶ Statement
+//! ```
+//!
+//! Errors carry the byte offset (plus line/column) of the offending token.
+//!
+mod ast;
+mod lexer;
+mod parser;
+
+pub use ast::*;
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{message} (line {line}, column {column})")]
+pub struct ParseError {
+    pub message: String,
+    /// Byte offset into the SQL text.
+    pub position: usize,
+    /// 1-based line of `position`.
+    pub line: usize,
+    /// 1-based column (in characters) of `position`.
+    pub column: usize,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, input: &str, position: usize) -> Self {
+        let before = &input[..position.min(input.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+        Self {
+            message: message.into(),
+            position,
+            line,
+            column,
+        }
+    }
+}
+
+/// Parse exactly one SQL statement (an optional trailing `;` is allowed).
+pub fn parse(sql: &str) -> Result<Statement, ParseError> {
+    parser::Parser::new(sql)?.parse_statement()
+}
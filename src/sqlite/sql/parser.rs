@@ -0,0 +1,1143 @@
+//! # sql/parser.rs – recursive-descent parser: tokens in, AST out
+//!
+//! ```text
+//!  statement  := (select | create_table | create_index) [;]
+//!  select     := SELECT result_col {, result_col}
+//!                [FROM table_ref {join_op table_ref [ON expr]}]
+//!                [WHERE expr]
+//!                [GROUP BY expr {, expr}] [HAVING expr]
+//!                [ORDER BY expr [ASC|DESC] [NULLS (FIRST|LAST)] {, ...}]
+//!                [LIMIT expr [(OFFSET | ,) expr]]
+//!  table_ref  := table [[AS] alias]
+//!  join_op    := , | [INNER | CROSS] JOIN | LEFT [OUTER] JOIN
+//!  create_index := CREATE [UNIQUE] INDEX [IF NOT EXISTS] name
+//!                  ON table ( indexed_col {, indexed_col} ) [WHERE expr]
+//!  indexed_col  := column [COLLATE name] [ASC|DESC]
+//!  create_table := CREATE [TEMP] TABLE [IF NOT EXISTS] name
+//!                  ( column_def {, column_def} {[,] table_constraint} )
+//!                  [WITHOUT ROWID | STRICT {, ...}]
+//!                | CREATE VIRTUAL TABLE [IF NOT EXISTS] name USING module [( ... )]
+//!  column_def   := name [type_name] {[CONSTRAINT name] column_constraint}
+//!  type_name    := word {word} [( number [, number] )]
+//!  column_constraint := PRIMARY KEY [ASC|DESC] [conflict] [AUTOINCREMENT]
+//!                     | [NOT] NULL [conflict] | UNIQUE [conflict]
+//!                     | CHECK ( ... ) | DEFAULT value | COLLATE name
+//!                     | REFERENCES fk | [GENERATED ALWAYS] AS ( ... ) [STORED|VIRTUAL]
+//!  table_constraint  := [CONSTRAINT name] ( PRIMARY KEY ( indexed_col {, ...} ) [conflict]
+//!                     | UNIQUE ( indexed_col {, ...} ) [conflict] | CHECK ( ... )
+//!                     | FOREIGN KEY ( name {, name} ) REFERENCES fk )
+//!
+//!  expr       := or
+//!  or         := and {OR and}
+//!  and        := not {AND not}
+//!  not        := NOT not | equality
+//!  equality   := comparison {(= | != | <>) comparison | IS [NOT] NULL
+//!                            | [NOT] BETWEEN comparison AND comparison}
+//!  comparison := additive {(< | <= | > | >=) additive}
+//!  additive   := multiplicative {(+ | -) multiplicative}
+//!  multiplicative := concat {(* | / | %) concat}
+//!  concat     := unary {|| unary}
+//!  unary      := (- | +) unary | primary
+//!  primary    := literal | ( expr ) | name [. name] | name ( [*] | args )
+//! ```
+//!
+//! Each rule is one method; lower rules bind tighter. Parts of CREATE TABLE
+//! that don't describe the stored columns (CHECK expressions, foreign key
+//! actions, conflict clauses) are only skipped over, not checked.
+//!
+use super::ast::*;
+use super::lexer::{Lexer, Token, TokenKind};
+use super::ParseError;
+
+/// Words that can never be used as a bare name. Other keywords such as
+/// `ASC`, `DESC`, `BY` or `OFFSET` fall back to plain names, as in SQLite.
+const RESERVED: &[&str] = &[
+    "AND", "AS", "BETWEEN", "FROM", "GROUP", "HAVING", "IS", "JOIN", "LIMIT", "NOT", "NULL", "ON",
+    "OR", "ORDER", "SELECT", "WHERE",
+];
+
+/// Join keywords are fine as table and column names, but never taken as an
+/// implicit alias: in `FROM a LEFT JOIN b` the `LEFT` starts the join.
+const JOIN_KEYWORDS: &[&str] = &[
+    "CROSS", "FULL", "INNER", "LEFT", "NATURAL", "OUTER", "RIGHT",
+];
+
+pub struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Result<Self, ParseError> {
+        let tokens = Lexer::new(input).tokenize()?;
+        Ok(Self {
+            input,
+            tokens,
+            pos: 0,
+        })
+    }
+
+    pub fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        let statement = if self.peek_keyword("SELECT") {
+            Statement::Select(self.parse_select()?)
+        } else if self.peek_keyword("CREATE") {
+            self.parse_create()?
+        } else {
+            return Err(self.unexpected("a statement"));
+        };
+
+        // A single trailing `;` is fine, anything else is not.
+        self.eat(&TokenKind::Semicolon);
+        if self.peek().kind != TokenKind::Eof {
+            return Err(self.unexpected("end of statement"));
+        }
+        Ok(statement)
+    }
+
+    // ---------------- token helpers ----------------
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: &TokenKind, what: &str) -> Result<(), ParseError> {
+        if self.eat(kind) {
+            Ok(())
+        } else {
+            Err(self.unexpected(what))
+        }
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Identifier(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(keyword))
+        }
+    }
+
+    /// Is the token `ahead` places past the current one this keyword?
+    fn keyword_at(&self, ahead: usize, keyword: &str) -> bool {
+        matches!(
+            self.tokens.get(self.pos + ahead).map(|token| &token.kind),
+            Some(TokenKind::Identifier(word)) if word.eq_ignore_ascii_case(keyword)
+        )
+    }
+
+    /// Source text from byte `start` up to the next token, as written.
+    fn text_since(&self, start: usize) -> String {
+        self.input[start..self.peek().position.max(start)]
+            .trim_end()
+            .to_string()
+    }
+
+    fn error(&self, message: impl Into<String>, position: usize) -> ParseError {
+        ParseError::new(message, self.input, position)
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        let token = self.peek();
+        let found = match &token.kind {
+            TokenKind::Eof => "end of input".to_string(),
+            _ => format!("\"{}\"", self.token_text(token)),
+        };
+        self.error(
+            format!("expected {}, found {}", expected, found),
+            token.position,
+        )
+    }
+
+    /// Source text of a token, used to quote it back in error messages.
+    fn token_text(&self, token: &Token) -> &str {
+        let end = self
+            .tokens
+            .get(self.pos + 1)
+            .filter(|next| next.position >= token.position)
+            .map_or(self.input.len(), |next| next.position);
+        self.input[token.position..end].trim_end()
+    }
+
+    /// Bare or quoted name. Reserved words are only accepted when quoted.
+    fn parse_name(&mut self, what: &str) -> Result<String, ParseError> {
+        match &self.peek().kind {
+            TokenKind::Identifier(word) if !is_reserved(word) => {
+                let word = word.clone();
+                self.advance();
+                Ok(word)
+            }
+            TokenKind::QuotedIdentifier(word) => {
+                let word = word.clone();
+                self.advance();
+                Ok(word)
+            }
+            _ => Err(self.unexpected(what)),
+        }
+    }
+
+    /// `[AS] alias` – the `AS` is optional, a string is accepted too.
+    fn parse_alias(&mut self) -> Result<Option<String>, ParseError> {
+        if self.eat_keyword("AS") {
+            if let TokenKind::String(alias) = &self.peek().kind {
+                let alias = alias.clone();
+                self.advance();
+                return Ok(Some(alias));
+            }
+            return self.parse_name("an alias").map(Some);
+        }
+        match &self.peek().kind {
+            TokenKind::Identifier(word)
+                if !is_reserved(word)
+                    && !JOIN_KEYWORDS.iter().any(|kw| kw.eq_ignore_ascii_case(word)) =>
+            {
+                self.parse_name("an alias").map(Some)
+            }
+            TokenKind::QuotedIdentifier(_) => self.parse_name("an alias").map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_table_ref(&mut self) -> Result<TableRef, ParseError> {
+        let name = self.parse_name("a table name")?;
+        let alias = self.parse_alias()?;
+        Ok(TableRef { name, alias })
+    }
+
+    /// `,` | `[INNER | CROSS] JOIN` | `LEFT [OUTER] JOIN`, or `None` when
+    /// the FROM clause ends here.
+    fn parse_join_operator(&mut self) -> Result<Option<JoinKind>, ParseError> {
+        if self.eat(&TokenKind::Comma) {
+            return Ok(Some(JoinKind::Inner));
+        }
+        let kind = if self.eat_keyword("LEFT") {
+            self.eat_keyword("OUTER");
+            JoinKind::Left
+        } else if self.eat_keyword("INNER")
+            || self.eat_keyword("CROSS")
+            || self.peek_keyword("JOIN")
+        {
+            JoinKind::Inner
+        } else {
+            return Ok(None);
+        };
+        self.expect_keyword("JOIN")?;
+        Ok(Some(kind))
+    }
+
+    // ---------------- statements ----------------
+
+    fn parse_select(&mut self) -> Result<Select, ParseError> {
+        self.expect_keyword("SELECT")?;
+
+        let mut columns = vec![self.parse_result_column()?];
+        while self.eat(&TokenKind::Comma) {
+            columns.push(self.parse_result_column()?);
+        }
+
+        let mut joins = Vec::new();
+        let from = if self.eat_keyword("FROM") {
+            let from = self.parse_table_ref()?;
+            while let Some(kind) = self.parse_join_operator()? {
+                let table = self.parse_table_ref()?;
+                let constraint = if self.eat_keyword("ON") {
+                    Some(self.parse_expr()?)
+                } else {
+                    None
+                };
+                joins.push(Join {
+                    kind,
+                    table,
+                    constraint,
+                });
+            }
+            Some(from)
+        } else {
+            None
+        };
+
+        let where_clause = if self.eat_keyword("WHERE") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+
+        let mut group_by = Vec::new();
+        if self.eat_keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            group_by.push(self.parse_expr()?);
+            while self.eat(&TokenKind::Comma) {
+                group_by.push(self.parse_expr()?);
+            }
+        }
+        // HAVING without GROUP BY makes the whole table one group.
+        let having = if self.eat_keyword("HAVING") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+
+        let mut order_by = Vec::new();
+        if self.eat_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                let expr = self.parse_expr()?;
+                let descending = if self.eat_keyword("DESC") {
+                    true
+                } else {
+                    self.eat_keyword("ASC");
+                    false
+                };
+                let nulls = if self.eat_keyword("NULLS") {
+                    if self.eat_keyword("FIRST") {
+                        Some(NullsOrder::First)
+                    } else {
+                        self.expect_keyword("LAST")?;
+                        Some(NullsOrder::Last)
+                    }
+                } else {
+                    None
+                };
+                order_by.push(OrderingTerm {
+                    expr,
+                    descending,
+                    nulls,
+                });
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+
+        let limit = if self.eat_keyword("LIMIT") {
+            let first = self.parse_expr()?;
+            if self.eat_keyword("OFFSET") {
+                let offset = self.parse_expr()?;
+                Some(Limit {
+                    count: first,
+                    offset: Some(offset),
+                })
+            } else if self.eat(&TokenKind::Comma) {
+                // `LIMIT a, b` means offset a, count b.
+                let count = self.parse_expr()?;
+                Some(Limit {
+                    count,
+                    offset: Some(first),
+                })
+            } else {
+                Some(Limit {
+                    count: first,
+                    offset: None,
+                })
+            }
+        } else {
+            None
+        };
+
+        Ok(Select {
+            columns,
+            from,
+            joins,
+            where_clause,
+            group_by,
+            having,
+            order_by,
+            limit,
+        })
+    }
+
+    fn parse_create(&mut self) -> Result<Statement, ParseError> {
+        self.expect_keyword("CREATE")?;
+        if self.peek_keyword("UNIQUE") || self.peek_keyword("INDEX") {
+            Ok(Statement::CreateIndex(self.parse_create_index()?))
+        } else {
+            Ok(Statement::CreateTable(self.parse_create_table()?))
+        }
+    }
+
+    fn parse_if_not_exists(&mut self) -> Result<(), ParseError> {
+        if self.eat_keyword("IF") {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+        }
+        Ok(())
+    }
+
+    fn parse_create_index(&mut self) -> Result<CreateIndex, ParseError> {
+        let unique = self.eat_keyword("UNIQUE");
+        self.expect_keyword("INDEX")?;
+        self.parse_if_not_exists()?;
+        let name = self.parse_qualified_name("an index name")?;
+        self.expect_keyword("ON")?;
+        let table = self.parse_name("a table name")?;
+        let columns = self.parse_indexed_columns()?;
+
+        let where_clause = if self.eat_keyword("WHERE") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+
+        Ok(CreateIndex {
+            name,
+            table,
+            unique,
+            columns,
+            where_clause,
+        })
+    }
+
+    /// `( column [COLLATE name] [ASC|DESC], ... )`
+    fn parse_indexed_columns(&mut self) -> Result<Vec<IndexedColumn>, ParseError> {
+        self.expect(&TokenKind::LeftParen, "\"(\"")?;
+        let mut columns = Vec::new();
+        loop {
+            let name = self.parse_name("a column name")?;
+            let collation = if self.eat_keyword("COLLATE") {
+                Some(self.parse_name("a collation name")?)
+            } else {
+                None
+            };
+            let descending = if self.eat_keyword("DESC") {
+                true
+            } else {
+                self.eat_keyword("ASC");
+                false
+            };
+            columns.push(IndexedColumn {
+                name,
+                collation,
+                descending,
+            });
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RightParen, "\")\"")?;
+        Ok(columns)
+    }
+
+    fn parse_create_table(&mut self) -> Result<CreateTable, ParseError> {
+        if !self.eat_keyword("TEMP") {
+            self.eat_keyword("TEMPORARY");
+        }
+        let is_virtual = self.eat_keyword("VIRTUAL");
+        self.expect_keyword("TABLE")?;
+        self.parse_if_not_exists()?;
+        let name = self.parse_qualified_name("a table name")?;
+
+        let mut table = CreateTable {
+            name,
+            columns: Vec::new(),
+            constraints: Vec::new(),
+            without_rowid: false,
+            strict: false,
+            module: None,
+        };
+        if is_virtual {
+            self.expect_keyword("USING")?;
+            table.module = Some(self.parse_name("a module name")?);
+            if self.peek().kind == TokenKind::LeftParen {
+                self.skip_parenthesized()?;
+            }
+            return Ok(table);
+        }
+
+        self.expect(&TokenKind::LeftParen, "\"(\"")?;
+        loop {
+            if self.peek_table_constraint() {
+                self.parse_table_constraint(&mut table.constraints)?;
+            } else if table.constraints.is_empty() {
+                table.columns.push(self.parse_column_def()?);
+            } else {
+                return Err(self.unexpected("a table constraint"));
+            }
+            // Table constraints may follow each other without a comma.
+            if !self.eat(&TokenKind::Comma) && !self.peek_table_constraint() {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RightParen, "\")\"")?;
+
+        loop {
+            if self.eat_keyword("WITHOUT") {
+                self.expect_keyword("ROWID")?;
+                table.without_rowid = true;
+            } else if self.eat_keyword("STRICT") {
+                table.strict = true;
+            } else {
+                break;
+            }
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+        Ok(table)
+    }
+
+    fn peek_table_constraint(&self) -> bool {
+        ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+            .iter()
+            .any(|keyword| self.peek_keyword(keyword))
+    }
+
+    fn parse_table_constraint(
+        &mut self,
+        constraints: &mut Vec<TableConstraint>,
+    ) -> Result<(), ParseError> {
+        if self.eat_keyword("CONSTRAINT") {
+            self.parse_name("a constraint name")?;
+        }
+        if self.eat_keyword("PRIMARY") {
+            self.expect_keyword("KEY")?;
+            let columns = self.parse_indexed_columns()?;
+            self.eat_keyword("AUTOINCREMENT");
+            self.skip_conflict_clause();
+            constraints.push(TableConstraint::PrimaryKey(columns));
+        } else if self.eat_keyword("UNIQUE") {
+            let columns = self.parse_indexed_columns()?;
+            self.skip_conflict_clause();
+            constraints.push(TableConstraint::Unique(columns));
+        } else if self.eat_keyword("CHECK") {
+            self.skip_parenthesized()?;
+        } else if self.eat_keyword("FOREIGN") {
+            self.expect_keyword("KEY")?;
+            self.skip_parenthesized()?;
+            self.expect_keyword("REFERENCES")?;
+            self.skip_foreign_key_clause()?;
+        } else {
+            return Err(self.unexpected("a table constraint"));
+        }
+        Ok(())
+    }
+
+    fn parse_column_def(&mut self) -> Result<ColumnDef, ParseError> {
+        let name = match &self.peek().kind {
+            // SQLite takes a string literal as a column name here.
+            TokenKind::String(name) => {
+                let name = name.clone();
+                self.advance();
+                name
+            }
+            _ => self.parse_name("a column name")?,
+        };
+
+        // The type is every word up to the first constraint, plus optional
+        // size arguments: `VARCHAR(255)`, `UNSIGNED BIG INT`, `DECIMAL(10,2)`.
+        let start = self.peek().position;
+        while matches!(&self.peek().kind, TokenKind::Identifier(word) if !is_constraint_start(word))
+        {
+            self.advance();
+        }
+        if self.peek().kind == TokenKind::LeftParen && self.peek().position > start {
+            self.skip_parenthesized()?;
+        }
+        let declared_type = self.text_since(start);
+
+        let mut column = ColumnDef {
+            name,
+            declared_type,
+            not_null: false,
+            default: None,
+            primary_key: None,
+            unique: false,
+            collation: None,
+        };
+        loop {
+            if self.eat_keyword("CONSTRAINT") {
+                self.parse_name("a constraint name")?;
+            } else if self.eat_keyword("PRIMARY") {
+                self.expect_keyword("KEY")?;
+                let descending = if self.eat_keyword("DESC") {
+                    true
+                } else {
+                    self.eat_keyword("ASC");
+                    false
+                };
+                self.skip_conflict_clause();
+                let autoincrement = self.eat_keyword("AUTOINCREMENT");
+                column.primary_key = Some(ColumnPrimaryKey {
+                    descending,
+                    autoincrement,
+                });
+            } else if self.eat_keyword("NOT") {
+                self.expect_keyword("NULL")?;
+                self.skip_conflict_clause();
+                column.not_null = true;
+            } else if self.eat_keyword("NULL") {
+                self.skip_conflict_clause();
+            } else if self.eat_keyword("UNIQUE") {
+                self.skip_conflict_clause();
+                column.unique = true;
+            } else if self.eat_keyword("CHECK") {
+                self.skip_parenthesized()?;
+            } else if self.eat_keyword("DEFAULT") {
+                column.default = Some(self.parse_default_value()?);
+            } else if self.eat_keyword("COLLATE") {
+                column.collation = Some(self.parse_name("a collation name")?);
+            } else if self.eat_keyword("REFERENCES") {
+                self.skip_foreign_key_clause()?;
+            } else if self.peek_keyword("GENERATED") || self.peek_keyword("AS") {
+                if self.eat_keyword("GENERATED") {
+                    self.expect_keyword("ALWAYS")?;
+                }
+                self.expect_keyword("AS")?;
+                self.skip_parenthesized()?;
+                if !self.eat_keyword("STORED") {
+                    self.eat_keyword("VIRTUAL");
+                }
+            } else {
+                return Ok(column);
+            }
+        }
+    }
+
+    /// `DEFAULT (expr)`, a signed number, a literal or a bare word such as
+    /// `CURRENT_TIMESTAMP`; kept as written.
+    fn parse_default_value(&mut self) -> Result<String, ParseError> {
+        let start = self.peek().position;
+        if self.peek().kind == TokenKind::LeftParen {
+            self.skip_parenthesized()?;
+        } else {
+            if !self.eat(&TokenKind::Minus) {
+                self.eat(&TokenKind::Plus);
+            }
+            match self.peek().kind {
+                TokenKind::Identifier(_)
+                | TokenKind::String(_)
+                | TokenKind::Integer(_)
+                | TokenKind::Real(_)
+                | TokenKind::Blob(_) => {
+                    self.advance();
+                }
+                _ => return Err(self.unexpected("a default value")),
+            }
+        }
+        Ok(self.text_since(start))
+    }
+
+    /// `table [(columns)]` followed by any `ON DELETE/UPDATE action`,
+    /// `MATCH name` and `[NOT] DEFERRABLE [INITIALLY ...]` clauses.
+    fn skip_foreign_key_clause(&mut self) -> Result<(), ParseError> {
+        self.parse_name("a table name")?;
+        if self.peek().kind == TokenKind::LeftParen {
+            self.skip_parenthesized()?;
+        }
+        loop {
+            if self.eat_keyword("ON") {
+                self.advance(); // DELETE | UPDATE
+                                // SET NULL | SET DEFAULT | NO ACTION | CASCADE | RESTRICT
+                if !self.eat_keyword("SET") {
+                    self.eat_keyword("NO");
+                }
+                self.advance();
+            } else if self.eat_keyword("MATCH")
+                || (self.peek_keyword("NOT") && self.keyword_at(1, "DEFERRABLE"))
+            {
+                // The MATCH name, or the NOT before DEFERRABLE.
+                self.advance();
+            } else if self.eat_keyword("DEFERRABLE") {
+                if self.eat_keyword("INITIALLY") {
+                    self.advance(); // DEFERRED | IMMEDIATE
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// `ON CONFLICT (ROLLBACK | ABORT | FAIL | IGNORE | REPLACE)`
+    fn skip_conflict_clause(&mut self) {
+        if self.peek_keyword("ON") && self.keyword_at(1, "CONFLICT") {
+            self.advance();
+            self.advance();
+            self.advance();
+        }
+    }
+
+    /// Skip a `( ... )` group, nested parentheses included.
+    fn skip_parenthesized(&mut self) -> Result<(), ParseError> {
+        self.expect(&TokenKind::LeftParen, "\"(\"")?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance().kind {
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen => depth -= 1,
+                TokenKind::Eof => return Err(self.unexpected("\")\"")),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// `name` or `schema.name`; only the last part is kept.
+    fn parse_qualified_name(&mut self, what: &str) -> Result<String, ParseError> {
+        let mut name = self.parse_name(what)?;
+        if self.eat(&TokenKind::Dot) {
+            name = self.parse_name(what)?;
+        }
+        Ok(name)
+    }
+
+    fn parse_result_column(&mut self) -> Result<ResultColumn, ParseError> {
+        if self.eat(&TokenKind::Star) {
+            return Ok(ResultColumn::Star);
+        }
+
+        // `table.*` needs two tokens of lookahead.
+        let is_table_star = matches!(
+            &self.peek().kind,
+            TokenKind::Identifier(_) | TokenKind::QuotedIdentifier(_)
+        ) && self.tokens.get(self.pos + 1).map(|t| &t.kind)
+            == Some(&TokenKind::Dot)
+            && self.tokens.get(self.pos + 2).map(|t| &t.kind) == Some(&TokenKind::Star);
+        if is_table_star {
+            let table = self.parse_name("a table name")?;
+            self.advance(); // .
+            self.advance(); // *
+            return Ok(ResultColumn::TableStar(table));
+        }
+
+        let start = self.peek().position;
+        let expr = self.parse_expr()?;
+        // Everything up to the next token, so `count( * )` keeps its spaces.
+        let text = self.text_since(start);
+        let alias = self.parse_alias()?;
+        Ok(ResultColumn::Expr { expr, alias, text })
+    }
+
+    // ---------------- expressions ----------------
+
+    pub fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_and()?;
+        while self.eat_keyword("OR") {
+            let right = self.parse_and()?;
+            left = binary(left, BinaryOp::Or, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_not()?;
+        while self.eat_keyword("AND") {
+            let right = self.parse_not()?;
+            left = binary(left, BinaryOp::And, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, ParseError> {
+        if self.eat_keyword("NOT") {
+            let expr = self.parse_not()?;
+            return Ok(Expr::Unary {
+                op: UnaryOp::Not,
+                expr: Box::new(expr),
+            });
+        }
+        self.parse_equality()
+    }
+
+    fn parse_equality(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_comparison()?;
+        loop {
+            if self.eat_keyword("IS") {
+                let negated = self.eat_keyword("NOT");
+                self.expect_keyword("NULL")?;
+                left = Expr::IsNull {
+                    expr: Box::new(left),
+                    negated,
+                };
+                continue;
+            }
+            if self.peek_keyword("BETWEEN")
+                || (self.peek_keyword("NOT") && self.keyword_at(1, "BETWEEN"))
+            {
+                let negated = self.eat_keyword("NOT");
+                self.expect_keyword("BETWEEN")?;
+                // The bounds stop short of AND, which belongs to BETWEEN.
+                let low = self.parse_comparison()?;
+                self.expect_keyword("AND")?;
+                let high = self.parse_comparison()?;
+                left = Expr::Between {
+                    expr: Box::new(left),
+                    low: Box::new(low),
+                    high: Box::new(high),
+                    negated,
+                };
+                continue;
+            }
+            let op = match self.peek().kind {
+                TokenKind::Eq => BinaryOp::Eq,
+                TokenKind::NotEq => BinaryOp::NotEq,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_comparison()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_additive()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Lt => BinaryOp::Lt,
+                TokenKind::LtEq => BinaryOp::LtEq,
+                TokenKind::Gt => BinaryOp::Gt,
+                TokenKind::GtEq => BinaryOp::GtEq,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_additive()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Subtract,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_multiplicative()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_concat()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOp::Multiply,
+                TokenKind::Slash => BinaryOp::Divide,
+                TokenKind::Percent => BinaryOp::Remainder,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_concat()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    fn parse_concat(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_unary()?;
+        while self.eat(&TokenKind::Concat) {
+            let right = self.parse_unary()?;
+            left = binary(left, BinaryOp::Concat, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        let op = match self.peek().kind {
+            TokenKind::Minus => UnaryOp::Negate,
+            TokenKind::Plus => UnaryOp::Plus,
+            _ => return self.parse_primary(),
+        };
+        self.advance();
+        let expr = self.parse_unary()?;
+
+        // Fold `-5` straight into a literal so it looks like any other constant.
+        Ok(match (op, expr) {
+            (UnaryOp::Negate, Expr::Literal(Literal::Integer(n))) => {
+                Expr::Literal(Literal::Integer(n.wrapping_neg()))
+            }
+            (UnaryOp::Negate, Expr::Literal(Literal::Real(f))) => Expr::Literal(Literal::Real(-f)),
+            (op, expr) => Expr::Unary {
+                op,
+                expr: Box::new(expr),
+            },
+        })
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        let token = self.peek().clone();
+        let literal = match token.kind {
+            TokenKind::Integer(n) => Literal::Integer(n),
+            TokenKind::Real(f) => Literal::Real(f),
+            TokenKind::String(s) => Literal::String(s),
+            TokenKind::Blob(b) => Literal::Blob(b),
+            TokenKind::Identifier(ref word) if word.eq_ignore_ascii_case("NULL") => Literal::Null,
+            TokenKind::LeftParen => {
+                self.advance();
+                let expr = self.parse_expr()?;
+                self.expect(&TokenKind::RightParen, "\")\"")?;
+                return Ok(expr);
+            }
+            TokenKind::Identifier(_) | TokenKind::QuotedIdentifier(_) => {
+                return self.parse_name_expr();
+            }
+            _ => return Err(self.unexpected("an expression")),
+        };
+        self.advance();
+        Ok(Expr::Literal(literal))
+    }
+
+    /// `column`, `table.column` or `function(args)`.
+    fn parse_name_expr(&mut self) -> Result<Expr, ParseError> {
+        let name = self.parse_name("an expression")?;
+
+        if self.eat(&TokenKind::LeftParen) {
+            if self.eat(&TokenKind::Star) {
+                self.expect(&TokenKind::RightParen, "\")\"")?;
+                return Ok(Expr::Function {
+                    name,
+                    args: Vec::new(),
+                    star: true,
+                });
+            }
+            let mut args = Vec::new();
+            if !self.eat(&TokenKind::RightParen) {
+                loop {
+                    args.push(self.parse_expr()?);
+                    if !self.eat(&TokenKind::Comma) {
+                        break;
+                    }
+                }
+                self.expect(&TokenKind::RightParen, "\")\"")?;
+            }
+            return Ok(Expr::Function {
+                name,
+                args,
+                star: false,
+            });
+        }
+
+        if self.eat(&TokenKind::Dot) {
+            let column = self.parse_name("a column name")?;
+            return Ok(Expr::Column {
+                table: Some(name),
+                name: column,
+            });
+        }
+
+        Ok(Expr::Column { table: None, name })
+    }
+}
+
+fn is_reserved(word: &str) -> bool {
+    RESERVED.iter().any(|kw| kw.eq_ignore_ascii_case(word))
+}
+
+/// Words that end a column's type name and start its constraints.
+fn is_constraint_start(word: &str) -> bool {
+    [
+        "CONSTRAINT",
+        "PRIMARY",
+        "NOT",
+        "NULL",
+        "UNIQUE",
+        "CHECK",
+        "DEFAULT",
+        "COLLATE",
+        "REFERENCES",
+        "GENERATED",
+        "AS",
+    ]
+    .iter()
+    .any(|keyword| keyword.eq_ignore_ascii_case(word))
+}
+
+fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+    Expr::Binary {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse;
+    use super::*;
+
+    fn select(sql: &str) -> Select {
+        match parse(sql) {
+            Ok(Statement::Select(select)) => select,
+            other => panic!("not a SELECT: {:?}", other),
+        }
+    }
+
+    /// The WHERE clause with every binary operation in parentheses.
+    fn grouping(condition: &str) -> String {
+        fn show(expr: &Expr) -> String {
+            match expr {
+                Expr::Literal(Literal::Integer(n)) => n.to_string(),
+                Expr::Literal(Literal::String(s)) => format!("'{}'", s),
+                Expr::Literal(Literal::Null) => "NULL".to_string(),
+                Expr::Column { name, .. } => name.clone(),
+                Expr::Unary { op, expr } => format!("({:?} {})", op, show(expr)),
+                Expr::Binary { left, op, right } => {
+                    format!("({} {:?} {})", show(left), op, show(right))
+                }
+                Expr::IsNull { expr, negated } => {
+                    format!(
+                        "({} IS {}NULL)",
+                        show(expr),
+                        if *negated { "NOT " } else { "" }
+                    )
+                }
+                Expr::Between {
+                    expr, low, high, ..
+                } => {
+                    format!("({} BETWEEN {} AND {})", show(expr), show(low), show(high))
+                }
+                other => format!("{:?}", other),
+            }
+        }
+        let select = select(&format!("SELECT * FROM t WHERE {}", condition));
+        show(&select.where_clause.unwrap())
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(
+            grouping("a + b * c - d"),
+            "((a Add (b Multiply c)) Subtract d)"
+        );
+        assert_eq!(grouping("a || b * 2"), "((a Concat b) Multiply 2)");
+        assert_eq!(
+            grouping("a / -b % c"),
+            "((a Divide (Negate b)) Remainder c)"
+        );
+        assert_eq!(
+            grouping("a + 1 < b = c > 2"),
+            "(((a Add 1) Lt b) Eq (c Gt 2))"
+        );
+        assert_eq!(
+            grouping("NOT a = 1 OR b AND c <> 2"),
+            "((Not (a Eq 1)) Or (b And (c NotEq 2)))"
+        );
+        assert_eq!(
+            grouping("a BETWEEN 1 + 1 AND 3 AND b IS NOT NULL"),
+            "((a BETWEEN (1 Add 1) AND 3) And (b IS NOT NULL))"
+        );
+        assert_eq!(grouping("(a OR b) AND c"), "((a Or b) And c)");
+    }
+
+    #[test]
+    fn errors_report_the_position_of_the_bad_token() {
+        let error = parse("SELECT a FROM t WHERE").unwrap_err();
+        assert_eq!(error.message, "expected an expression, found end of input");
+        assert_eq!((error.position, error.column), (21, 22));
+
+        let error = parse("SELECT a,\n  b c d FROM t").unwrap_err();
+        assert_eq!(error.message, "expected end of statement, found \"d\"");
+        assert_eq!((error.position, error.line, error.column), (16, 2, 7));
+        assert_eq!(
+            error.to_string(),
+            "expected end of statement, found \"d\" (line 2, column 7)"
+        );
+
+        let error = parse("SELECT a FROM t ORDER a").unwrap_err();
+        assert_eq!(error.message, "expected BY, found \"a\"");
+        assert_eq!(error.position, 22);
+    }
+
+    #[test]
+    fn accepts_quoted_identifiers_as_names() {
+        let select =
+            select(r#"SELECT "first name", [t].[x y], `z` AS "w" FROM "my table" [alias]"#);
+        let columns: Vec<_> = select
+            .columns
+            .iter()
+            .map(|column| match column {
+                ResultColumn::Expr { expr, alias, .. } => (expr.clone(), alias.clone()),
+                other => panic!("unexpected column {:?}", other),
+            })
+            .collect();
+        let column = |table: Option<&str>, name: &str| Expr::Column {
+            table: table.map(str::to_string),
+            name: name.to_string(),
+        };
+        assert_eq!(
+            columns,
+            vec![
+                (column(None, "first name"), None),
+                (column(Some("t"), "x y"), None),
+                (column(None, "z"), Some("w".to_string())),
+            ]
+        );
+        let from = select.from.unwrap();
+        assert_eq!(from.name, "my table");
+        assert_eq!(from.alias.as_deref(), Some("alias"));
+
+        // Quoting is what lets a reserved word through.
+        assert!(parse("SELECT \"from\", [select], `where` FROM \"order\"").is_ok());
+        assert!(parse("SELECT from FROM t").is_err());
+    }
+
+    #[test]
+    fn limit_with_a_comma_puts_the_offset_first() {
+        let limit = select("SELECT * FROM t LIMIT 5, 10").limit.unwrap();
+        assert_eq!(limit.count, Expr::Literal(Literal::Integer(10)));
+        assert_eq!(limit.offset, Some(Expr::Literal(Literal::Integer(5))));
+
+        let limit = select("SELECT * FROM t LIMIT 10 OFFSET 5").limit.unwrap();
+        assert_eq!(limit.count, Expr::Literal(Literal::Integer(10)));
+        assert_eq!(limit.offset, Some(Expr::Literal(Literal::Integer(5))));
+    }
+
+    #[test]
+    fn fallback_keywords_work_as_column_names() {
+        let Ok(Statement::CreateTable(table)) =
+            parse("CREATE TABLE k (desc TEXT, offset INT, left, asc, by, key)")
+        else {
+            panic!("CREATE TABLE did not parse");
+        };
+        let names: Vec<_> = table.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["desc", "offset", "left", "asc", "by", "key"]);
+
+        let ordered = select("SELECT desc, left FROM k ORDER BY desc DESC LIMIT 1 OFFSET offset");
+        assert_eq!(ordered.columns.len(), 2);
+        assert!(ordered.order_by[0].descending);
+        assert_eq!(
+            ordered.limit.unwrap().offset,
+            Some(Expr::Column {
+                table: None,
+                name: "offset".to_string()
+            })
+        );
+
+        // A join keyword after a table starts the join instead of naming it.
+        let joined = select("SELECT * FROM a left JOIN b ON a.id = b.id");
+        assert_eq!(joined.from.unwrap().alias, None);
+        assert_eq!(joined.joins[0].kind, JoinKind::Left);
+    }
+}
@@ -0,0 +1,141 @@
+//! # sqlite/varint.rs – SQLite's 1 to 9 byte integers
+//!
+//! ```text
+//!  bytes 1-8:  [1|7 bits] [1|7 bits] … [0|7 bits]   high bit = more follow
+//!  byte 9:     [8 bits]                             only if 8 came before
+//! ```
+//!
+//! Big-endian groups of 7 bits, most significant first. Eight such groups
+//! only hold 56 bits, so a ninth byte, if there is one, contributes all 8 of
+//! its bits and ends the number whatever its high bit says. That is what
+//! lets a full 64-bit value (a negative rowid, say) fit in nine bytes.
+//!
+//! Values are `u64`; signed keys such as rowids are the same bits read back
+//! as `i64`.
+//!
+
+/// The longest a varint can be.
+pub const MAX_VARINT_LEN: usize = 9;
+
+/// Decode the varint at the start of `bytes`, returning its value and
+/// length, or `None` if `bytes` ends before the varint does.
+pub fn read(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().take(MAX_VARINT_LEN).enumerate() {
+        if i == MAX_VARINT_LEN - 1 {
+            return Some(((value << 8) | byte as u64, MAX_VARINT_LEN));
+        }
+        value = (value << 7) | (byte & 0x7F) as u64;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Append the shortest encoding of `value` to `out` and return its length.
+/// Only tests build records, so only tests need it.
+#[cfg(test)]
+pub(crate) fn write(value: u64, out: &mut Vec<u8>) -> usize {
+    // Anything wider than 56 bits takes all nine bytes: the last one holds
+    // the low 8 bits, the 8 before it 7 bits each.
+    if value >> 56 != 0 {
+        let high = value >> 8;
+        out.extend((0..8).rev().map(|group| (high >> (7 * group)) as u8 | 0x80));
+        out.push(value as u8);
+        return MAX_VARINT_LEN;
+    }
+    let len = (1..MAX_VARINT_LEN)
+        .find(|&len| value >> (7 * len) == 0)
+        .unwrap_or(MAX_VARINT_LEN - 1);
+    out.extend((0..len).rev().map(|group| {
+        let bits = (value >> (7 * group)) as u8 & 0x7F;
+        if group == 0 {
+            bits
+        } else {
+            bits | 0x80
+        }
+    }));
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        let len = write(value, &mut out);
+        assert_eq!(len, out.len());
+        out
+    }
+
+    #[test]
+    fn encodes_the_spec_examples() {
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(0x7F), [0x7F]);
+        assert_eq!(encode(0x80), [0x81, 0x00]);
+        assert_eq!(encode(0x3FFF), [0xFF, 0x7F]);
+        assert_eq!(encode(0x4000), [0x81, 0x80, 0x00]);
+        assert_eq!(
+            encode((1 << 56) - 1),
+            [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F]
+        );
+        // -1 as a rowid: nine bytes, the last carrying a full 8 bits.
+        assert_eq!(encode(u64::MAX), [0xFF; 9]);
+        assert_eq!(
+            encode(1 << 56),
+            [0x80, 0xC0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00]
+        );
+    }
+
+    #[test]
+    fn the_ninth_byte_keeps_all_eight_bits() {
+        // Its high bit would mean "more follows" anywhere else.
+        let bytes = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0xFF, 0x01];
+        assert_eq!(read(&bytes), Some((0xFF, 9)));
+        assert_eq!(read(&[0xFF; 9]), Some((u64::MAX, 9)));
+        assert_eq!(read(&[0xFF; 8]), None);
+    }
+
+    #[test]
+    fn reports_truncated_varints() {
+        assert_eq!(read(&[]), None);
+        assert_eq!(read(&[0x81]), None);
+        assert_eq!(read(&[0x81, 0x80]), None);
+        assert_eq!(read(&[0x81, 0x00, 0x55]), Some((0x80, 2)));
+    }
+
+    #[test]
+    fn round_trips_every_width() {
+        // Powers of two and their neighbours hit every length boundary; a
+        // xorshift stream fills in values with all kinds of bit patterns.
+        let mut values = vec![0, u64::MAX, i64::MIN as u64, i64::MAX as u64];
+        for shift in 0..64 {
+            let power = 1u64 << shift;
+            values.extend([power - 1, power, power + 1, power.wrapping_neg()]);
+        }
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        for _ in 0..10_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            // Vary the width too, or nearly every value would take 9 bytes.
+            values.push(state >> (state % 64));
+        }
+
+        for value in values {
+            let bytes = encode(value);
+            let expected_len = match 64 - value.leading_zeros() {
+                bits @ 0..=56 => (bits.max(1) as usize).div_ceil(7),
+                _ => 9,
+            };
+            assert_eq!(bytes.len(), expected_len, "length of {:#x}", value);
+            assert_eq!(read(&bytes), Some((value, bytes.len())), "{:#x}", value);
+            // Trailing bytes don't change what's read.
+            let mut padded = bytes.clone();
+            padded.extend([0xFF, 0x00]);
+            assert_eq!(read(&padded), Some((value, bytes.len())), "{:#x}", value);
+        }
+    }
+}
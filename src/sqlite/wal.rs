@@ -0,0 +1,272 @@
+//! # sqlite/wal.rs – reading committed pages out of a `-wal` file
+//!
+//! ```text
+//!  <db>-wal:  [32-byte header][frame 1][frame 2] … [frame N]
+//!  frame:     [24-byte frame header][one page of data]
+//!
+//!  header  0 magic   4 version   8 page size   12 checkpoint seq
+//!         16 salt-1  20 salt-2  24 checksum-1  28 checksum-2
+//!  frame   0 page number   4 db size after commit (0 = not a commit)
+//!          8 salt-1  12 salt-2  16 checksum-1  20 checksum-2
+//! ```
+//!
+//! In WAL mode writers append changed pages here instead of touching the
+//! database file, and a checkpoint copies them back later. A reader takes
+//! the newest copy of each page from the WAL, falling back to the main file.
+//!
+//! The file is scanned once, when the database is opened. A frame only
+//! counts if its salts match the header and the running checksum (seeded
+//! by the header's, then carried frame to frame) matches; the first frame
+//! that fails ends the log. Frames after the last commit frame belong to a
+//! transaction that never finished and are ignored too. What's left is a
+//! snapshot of the last commit as of open time.
+//!
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+};
+
+use super::error::{Error, Result};
+
+pub const WAL_HEADER_SIZE: usize = 32;
+pub const FRAME_HEADER_SIZE: usize = 24;
+
+/// Magic number with little-endian checksums; `| 1` means big-endian.
+const MAGIC: u32 = 0x377f_0682;
+/// The only WAL format version there is.
+const VERSION: u32 = 3_007_000;
+
+/// The committed frames of a write-ahead log.
+#[derive(Debug)]
+pub(crate) struct Wal {
+    file: RefCell<File>,
+    page_size: u32,
+    salts: [u8; 8],
+    /// Byte offset of the newest committed frame holding each page.
+    frames: HashMap<usize, u64>,
+    /// Database size in pages after the last commit, if there was one.
+    page_count: Option<usize>,
+}
+
+impl Wal {
+    /// Open `path` and index its committed frames. Returns `None` when there
+    /// is no WAL file or its header isn't valid; SQLite ignores such a log
+    /// too.
+    pub(crate) fn open(path: &str) -> Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let mut reader = BufReader::new(&file);
+
+        let mut header = [0; WAL_HEADER_SIZE];
+        if !read_full(&mut reader, &mut header)? {
+            return Ok(None);
+        }
+        let magic = be_u32(&header[0..4]);
+        if magic & !1 != MAGIC || be_u32(&header[4..8]) != VERSION {
+            return Ok(None);
+        }
+        let page_size = be_u32(&header[8..12]);
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return Ok(None);
+        }
+        let big_endian = magic & 1 == 1;
+        let mut sum = checksum(big_endian, (0, 0), &header[..24]);
+        if sum != (be_u32(&header[24..28]), be_u32(&header[28..32])) {
+            return Ok(None);
+        }
+        let mut salts = [0; 8];
+        salts.copy_from_slice(&header[16..24]);
+
+        let mut frames = HashMap::new();
+        let mut pending = HashMap::new();
+        let mut page_count = None;
+        let mut frame_header = [0; FRAME_HEADER_SIZE];
+        let mut page = vec![0; page_size as usize];
+        let mut offset = WAL_HEADER_SIZE as u64;
+        loop {
+            if !read_full(&mut reader, &mut frame_header)? || !read_full(&mut reader, &mut page)? {
+                break;
+            }
+            if frame_header[8..16] != salts {
+                break;
+            }
+            sum = checksum(big_endian, sum, &frame_header[..8]);
+            sum = checksum(big_endian, sum, &page);
+            if sum != (be_u32(&frame_header[16..20]), be_u32(&frame_header[20..24])) {
+                break;
+            }
+
+            let page_number = be_u32(&frame_header[0..4]) as usize;
+            if page_number == 0 {
+                break;
+            }
+            pending.insert(page_number, offset);
+            let commit_size = be_u32(&frame_header[4..8]);
+            if commit_size != 0 {
+                frames.extend(pending.drain());
+                page_count = Some(commit_size as usize);
+            }
+            offset += (FRAME_HEADER_SIZE + page_size as usize) as u64;
+        }
+
+        Ok(Some(Self {
+            file: RefCell::new(file),
+            page_size,
+            salts,
+            frames,
+            page_count,
+        }))
+    }
+
+    /// Page size in bytes, from the WAL header.
+    pub(crate) fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    /// Database size in pages as of the last commit in the log.
+    pub(crate) fn page_count(&self) -> Option<usize> {
+        self.page_count
+    }
+
+    /// The committed copy of `page_number`, if the log has one.
+    pub(crate) fn read_page(&self, page_number: usize) -> Result<Option<Vec<u8>>> {
+        let Some(&offset) = self.frames.get(&page_number) else {
+            return Ok(None);
+        };
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(offset))?;
+        let mut frame_header = [0; FRAME_HEADER_SIZE];
+        file.read_exact(&mut frame_header)?;
+        // A checkpoint that restarts the log lets writers overwrite it from
+        // the top with new salts; our frame offsets then point at other data.
+        if frame_header[8..16] != self.salts || be_u32(&frame_header[0..4]) as usize != page_number
+        {
+            return Err(Error::StaleSnapshot { page: page_number });
+        }
+        let mut page = vec![0; self.page_size as usize];
+        file.read_exact(&mut page)?;
+        Ok(Some(page))
+    }
+}
+
+/// Fill `buf`, or return `false` if the file ends first.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// The WAL checksum: two running 32-bit sums over pairs of words, read in
+/// the byte order the magic number asks for.
+fn checksum(big_endian: bool, (mut s0, mut s1): (u32, u32), data: &[u8]) -> (u32, u32) {
+    let word = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+    for pair in data.chunks_exact(8) {
+        s0 = s0.wrapping_add(word(&pair[0..4]).wrapping_add(s1));
+        s1 = s1.wrapping_add(word(&pair[4..8]).wrapping_add(s0));
+    }
+    (s0, s1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const PAGE_SIZE: u32 = 512;
+
+    struct WalBuilder {
+        bytes: Vec<u8>,
+        sum: (u32, u32),
+    }
+
+    impl WalBuilder {
+        fn new() -> Self {
+            let mut bytes = Vec::new();
+            for word in [MAGIC | 1, VERSION, PAGE_SIZE, 0, 0x1234, 0x5678] {
+                bytes.extend_from_slice(&word.to_be_bytes());
+            }
+            let sum = checksum(true, (0, 0), &bytes);
+            bytes.extend_from_slice(&sum.0.to_be_bytes());
+            bytes.extend_from_slice(&sum.1.to_be_bytes());
+            Self { bytes, sum }
+        }
+
+        fn frame(mut self, page_number: u32, commit_size: u32, fill: u8) -> Self {
+            let mut header = Vec::new();
+            header.extend_from_slice(&page_number.to_be_bytes());
+            header.extend_from_slice(&commit_size.to_be_bytes());
+            let page = vec![fill; PAGE_SIZE as usize];
+            self.sum = checksum(true, self.sum, &header);
+            self.sum = checksum(true, self.sum, &page);
+            header.extend_from_slice(&self.bytes[16..24]);
+            header.extend_from_slice(&self.sum.0.to_be_bytes());
+            header.extend_from_slice(&self.sum.1.to_be_bytes());
+            self.bytes.extend_from_slice(&header);
+            self.bytes.extend_from_slice(&page);
+            self
+        }
+
+        fn open(&self, name: &str) -> Option<Wal> {
+            let path =
+                std::env::temp_dir().join(format!("wal-test-{}-{}", std::process::id(), name));
+            File::create(&path).unwrap().write_all(&self.bytes).unwrap();
+            let wal = Wal::open(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            wal
+        }
+    }
+
+    fn page_fill(wal: &Wal, page_number: usize) -> Option<u8> {
+        wal.read_page(page_number).unwrap().map(|page| page[0])
+    }
+
+    #[test]
+    fn serves_the_newest_committed_frame() {
+        let wal = WalBuilder::new()
+            .frame(2, 0, 0xA1)
+            .frame(3, 3, 0xA2)
+            .frame(2, 3, 0xB1)
+            // Never committed.
+            .frame(3, 0, 0xC1)
+            .open("newest")
+            .unwrap();
+        assert_eq!(wal.page_count(), Some(3));
+        assert_eq!(page_fill(&wal, 2), Some(0xB1));
+        assert_eq!(page_fill(&wal, 3), Some(0xA2));
+        assert_eq!(page_fill(&wal, 1), None);
+    }
+
+    #[test]
+    fn stops_at_the_first_bad_checksum() {
+        let mut builder = WalBuilder::new().frame(2, 2, 0xA1).frame(2, 2, 0xB1);
+        let last = builder.bytes.len() - 1;
+        builder.bytes[last] ^= 0xFF;
+        let wal = builder.open("checksum").unwrap();
+        assert_eq!(page_fill(&wal, 2), Some(0xA1));
+    }
+
+    #[test]
+    fn ignores_a_log_with_a_bad_header() {
+        let mut builder = WalBuilder::new().frame(2, 2, 0xA1);
+        builder.bytes[20] ^= 0xFF;
+        assert!(builder.open("header").is_none());
+    }
+}
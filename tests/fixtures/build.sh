@@ -0,0 +1,75 @@
+#!/bin/sh
+# Rebuild the small databases the integration tests read. Needs sqlite3;
+# the .db files are checked in so the tests themselves don't.
+set -e
+cd "$(dirname "$0")"
+
+# Values far bigger than a 512-byte page, so rows and index entries spill
+# onto overflow chains. The numbered chunks show if pieces come back out
+# of order.
+rm -f overflow.db
+sqlite3 overflow.db <<'SQL'
+PRAGMA page_size = 512;
+CREATE TABLE docs (id INTEGER PRIMARY KEY, title TEXT, body TEXT, data BLOB);
+CREATE INDEX docs_title ON docs (title);
+CREATE INDEX docs_body ON docs (body);
+INSERT INTO docs VALUES (1, 'short', 'tiny', x'00');
+WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 599)
+INSERT INTO docs
+SELECT 2, 'long', group_concat(printf('%04d ', i), ''),
+       CAST(group_concat(printf('%03x;', i), '') AS BLOB)
+FROM n;
+INSERT INTO docs VALUES (3, 'after', 'small', NULL);
+SQL
+
+# Two tables to join: by the INTEGER PRIMARY KEY of customers, or by the
+# index on orders.customer_id. Cy has no orders; order 13 has no customer.
+rm -f joins.db
+sqlite3 joins.db <<'SQL'
+PRAGMA page_size = 512;
+CREATE TABLE customers (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE orders (id INTEGER PRIMARY KEY, customer_id INTEGER, item TEXT);
+CREATE INDEX orders_customer ON orders (customer_id);
+INSERT INTO customers VALUES (1, 'Ann'), (2, 'Bob'), (3, 'Cy');
+INSERT INTO orders VALUES (10, 1, 'apple'), (11, 2, 'pear'), (12, 1, 'plum'), (13, 4, 'fig');
+SQL
+
+# sqlite3 takes a quoted type name, which our parser doesn't; that table
+# shouldn't stop the others from being read. WITHOUT ROWID tables keep
+# their rows in an index B-tree, which queries don't read.
+rm -f schema.db
+sqlite3 schema.db <<'SQL'
+CREATE TABLE good (id INTEGER PRIMARY KEY, v TEXT);
+CREATE TABLE odd (a 'text');
+CREATE TABLE later (w);
+CREATE TABLE keyed (k TEXT PRIMARY KEY, v) WITHOUT ROWID;
+INSERT INTO good VALUES (1, 'one'), (2, 'two');
+INSERT INTO odd VALUES ('x');
+INSERT INTO later VALUES (3);
+INSERT INTO keyed VALUES ('a', 1);
+SQL
+
+# A UTF-16LE database, where text sorts by its little-endian bytes: U+0100
+# ('Ā', 00 01) comes before 'b' (62 00), and so does the surrogate pair of
+# '😀' (3D D8 00 DE).
+rm -f utf16le.db
+sqlite3 utf16le.db <<'SQL'
+PRAGMA encoding = 'UTF-16le';
+CREATE TABLE u (id INTEGER PRIMARY KEY, s TEXT);
+CREATE INDEX u_s ON u (s);
+INSERT INTO u (s) VALUES ('b'), ('héllo'), ('zz'), ('Ā'), ('😀');
+SQL
+
+# A table three levels deep on 512-byte pages, with a gap between every
+# two rowids: 2, 4, 6, … 8000. The B-tree tests walk and seek through it
+# across leaf and interior page boundaries. Its indexes are as deep, and
+# sort 'row 10' before 'row 2', so index order isn't rowid order.
+rm -f deep.db
+sqlite3 deep.db <<'SQL'
+PRAGMA page_size = 512;
+CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT, g INTEGER);
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 4000)
+INSERT INTO t SELECT 2 * i, printf('row %d', i), i % 7 FROM n;
+CREATE INDEX t_v ON t (v);
+CREATE INDEX t_gv ON t (g, v);
+SQL
@@ -0,0 +1,448 @@
+use codecrafters_sqlite::{Affinity, Database, Error, QueryError, RecordValue};
+
+fn sample() -> Database {
+    Database::load(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap()
+}
+
+/// One of the small databases made by `tests/fixtures/build.sh`.
+fn fixture(name: &str) -> Database {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    Database::load(&path).unwrap()
+}
+
+fn all_rows(db: &Database, sql: &str) -> Vec<Vec<RecordValue>> {
+    db.query(sql)
+        .unwrap()
+        .map(|row| row.unwrap().into_values())
+        .collect()
+}
+
+#[test]
+fn query_returns_named_columns_and_rows() {
+    let db = sample();
+    let rows = db
+        .query("SELECT id, name AS n, count(*) FROM apples GROUP BY id ORDER BY id LIMIT 2")
+        .unwrap();
+    assert_eq!(rows.columns(), ["id", "n", "count(*)"]);
+
+    let rows: Vec<_> = rows.map(|row| row.unwrap().into_values()).collect();
+    assert_eq!(
+        rows,
+        [
+            vec![
+                RecordValue::Int(1),
+                RecordValue::Text("Granny Smith".to_string()),
+                RecordValue::Int(1),
+            ],
+            vec![
+                RecordValue::Int(2),
+                RecordValue::Text("Fuji".to_string()),
+                RecordValue::Int(1),
+            ],
+        ]
+    );
+}
+
+/// What running `sql` fails with, whether `query` itself or one of the
+/// rows reports it.
+fn query_error(db: &Database, sql: &str) -> QueryError {
+    match db
+        .query(sql)
+        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+    {
+        Err(Error::Query(err)) => err,
+        Err(err) => panic!("expected a query error from {}, got {:?}", sql, err),
+        Ok(rows) => panic!("expected {} to fail, got {} rows", sql, rows.len()),
+    }
+}
+
+#[test]
+fn query_reports_typed_errors() {
+    let db = sample();
+    assert!(matches!(db.query("SELECT FROM"), Err(Error::Parse(_))));
+
+    let cases = [
+        (
+            "SELECT * FROM pears",
+            QueryError::NoSuchTable("pears".to_string()),
+        ),
+        (
+            "SELECT shape FROM apples",
+            QueryError::NoSuchColumn("shape".to_string()),
+        ),
+        (
+            "SELECT a.shape FROM apples a",
+            QueryError::NoSuchColumn("a.shape".to_string()),
+        ),
+        (
+            "SELECT name FROM apples, oranges",
+            QueryError::AmbiguousColumn("name".to_string()),
+        ),
+        (
+            "SELECT upper(name) FROM apples",
+            QueryError::NoSuchFunction("upper".to_string()),
+        ),
+        (
+            "SELECT count(id, name) FROM apples",
+            QueryError::WrongArgumentCount("count".to_string()),
+        ),
+        (
+            "SELECT name FROM apples WHERE count(*) > 1",
+            QueryError::MisusedAggregate("count".to_string()),
+        ),
+        (
+            "SELECT sum(9223372036854775807) FROM apples",
+            QueryError::IntegerOverflow,
+        ),
+        (
+            "SELECT name FROM apples ORDER BY 2",
+            QueryError::TermOutOfRange {
+                clause: "ORDER BY",
+                position: 2,
+            },
+        ),
+        (
+            "CREATE TABLE pears (id INTEGER PRIMARY KEY)",
+            QueryError::ReadOnly,
+        ),
+    ];
+    for (sql, expected) in cases {
+        assert_eq!(query_error(&db, sql), expected, "{}", sql);
+    }
+    assert!(matches!(
+        query_error(&db, "SELECT name FROM apples LIMIT 'two'"),
+        QueryError::DatatypeMismatch(_)
+    ));
+}
+
+#[test]
+fn rowid_names_read_the_integer_primary_key() {
+    let db = sample();
+    let rows = db
+        .query("SELECT rowid, oid, _rowid_, name FROM apples WHERE _rowid_ = 3 ORDER BY oid")
+        .unwrap();
+    assert_eq!(rows.columns(), ["id", "id", "id", "name"]);
+    let rows: Vec<_> = rows.map(|row| row.unwrap().into_values()).collect();
+    assert_eq!(
+        rows,
+        [vec![
+            RecordValue::Int(3),
+            RecordValue::Int(3),
+            RecordValue::Int(3),
+            RecordValue::Text("Honeycrisp".to_string()),
+        ]]
+    );
+}
+
+#[test]
+fn schema_describes_tables_and_columns() {
+    let db = sample();
+    let schema = db.schema().unwrap();
+    let names: Vec<&str> = schema
+        .tables()
+        .iter()
+        .map(|table| table.name.as_str())
+        .collect();
+    assert_eq!(names, ["apples", "sqlite_sequence", "oranges"]);
+
+    let apples = schema.table("APPLES").unwrap();
+    assert_eq!(apples.root_page, 2);
+    let columns: Vec<(&str, &str, Affinity, bool)> = apples
+        .columns
+        .iter()
+        .map(|c| {
+            let declared = c.declared_type.as_str();
+            (c.name.as_str(), declared, c.affinity, c.primary_key)
+        })
+        .collect();
+    assert_eq!(
+        columns,
+        [
+            ("id", "integer", Affinity::Integer, true),
+            ("name", "text", Affinity::Text, false),
+            ("color", "text", Affinity::Text, false),
+        ]
+    );
+    assert!(schema.indexes().is_empty());
+}
+
+#[test]
+fn a_table_that_does_not_parse_leaves_the_rest_readable() {
+    let db = fixture("schema.db");
+    let schema = db.schema().unwrap();
+    let odd = schema.table("odd").unwrap();
+    assert_eq!(odd.sql, "CREATE TABLE odd (a 'text')");
+    assert!(odd.columns.is_empty());
+    assert!(schema.table("good").unwrap().error().is_none());
+
+    assert_eq!(
+        all_rows(&db, "SELECT v FROM good ORDER BY id"),
+        [vec![text("one")], vec![text("two")]]
+    );
+    assert_eq!(
+        all_rows(&db, "SELECT w FROM later"),
+        [vec![RecordValue::Int(3)]]
+    );
+    match db.query("SELECT * FROM odd") {
+        Err(Error::MalformedSchema { name, .. }) => assert_eq!(name, "odd"),
+        other => panic!(
+            "expected a malformed schema error, got {:?}",
+            other.map(|rows| rows.count())
+        ),
+    };
+}
+
+#[test]
+fn without_rowid_tables_are_refused() {
+    let db = fixture("schema.db");
+    assert!(db.schema().unwrap().table("keyed").unwrap().without_rowid);
+    assert_eq!(
+        query_error(&db, "SELECT v FROM keyed"),
+        QueryError::Unsupported("WITHOUT ROWID tables are not supported".to_string())
+    );
+}
+
+#[test]
+fn cursor_walks_a_table_in_rowid_order() {
+    let db = sample();
+    let root = db.schema().unwrap().table("oranges").unwrap().root_page;
+    let ids: Vec<i64> = db
+        .table_cursor(root)
+        .map(|record| record.unwrap().id)
+        .collect();
+    assert_eq!(ids, [1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn rowid_ranges_seek_instead_of_scanning() {
+    let db = sample();
+    let root = db.schema().unwrap().table("oranges").unwrap().root_page;
+    let ids: Vec<i64> = db
+        .table_cursor(root)
+        .range(2..5)
+        .map(|record| record.unwrap().id)
+        .collect();
+    assert_eq!(ids, [2, 3, 4]);
+
+    let names: Vec<String> = db
+        .query("SELECT name FROM apples WHERE id BETWEEN 1.5 AND 3")
+        .unwrap()
+        .map(|row| row.unwrap().values()[0].to_string())
+        .collect();
+    assert_eq!(names, ["Fuji", "Honeycrisp"]);
+    let rows = db
+        .query("SELECT id FROM apples WHERE id > 2 AND id NOT BETWEEN 3 AND 3")
+        .unwrap()
+        .count();
+    assert_eq!(rows, 1);
+}
+
+#[test]
+fn rows_are_read_as_they_are_asked_for() {
+    // Table `t` of deep.db is three levels deep, with more than a hundred
+    // leaves.
+    let misses = |db: &Database| db.cache_stats().misses;
+    let db = fixture("deep.db");
+    let before = misses(&db);
+    let mut rows = db.query("SELECT v FROM t").unwrap();
+    assert_eq!(rows.next().unwrap().unwrap().values(), [text("row 1")]);
+    assert_eq!(rows.next().unwrap().unwrap().values(), [text("row 2")]);
+    assert!(
+        misses(&db) - before <= 3,
+        "{} pages read",
+        misses(&db) - before
+    );
+
+    let before = misses(&db);
+    assert_eq!(
+        db.query("SELECT v FROM t LIMIT 2 OFFSET 5")
+            .unwrap()
+            .count(),
+        2
+    );
+    assert!(
+        misses(&db) - before <= 3,
+        "{} pages read",
+        misses(&db) - before
+    );
+
+    // A bad column only shows up once there is a row to read it from, and
+    // ends the iteration.
+    let mut rows = db.query("SELECT v FROM t WHERE missing = 1").unwrap();
+    match rows.next() {
+        Some(Err(Error::Query(QueryError::NoSuchColumn(name)))) => assert_eq!(name, "missing"),
+        other => panic!("expected a missing column, got {:?}", other),
+    }
+    assert!(rows.next().is_none());
+}
+
+#[test]
+fn order_by_an_index_stops_reading_it_at_the_limit() {
+    // deep.db's indexes on `t` are three levels and 130-odd pages each.
+    let misses = |db: &Database| db.cache_stats().misses;
+    let db = fixture("deep.db");
+    let before = misses(&db);
+    assert_eq!(
+        all_rows(&db, "SELECT id, v FROM t ORDER BY v LIMIT 2 OFFSET 1"),
+        [
+            vec![RecordValue::Int(20), text("row 10")],
+            vec![RecordValue::Int(200), text("row 100")],
+        ]
+    );
+    // Down the index, and three rowid seeks down the table.
+    assert!(
+        misses(&db) - before <= 9,
+        "{} pages read",
+        misses(&db) - before
+    );
+
+    let db = fixture("deep.db");
+    let before = misses(&db);
+    assert_eq!(
+        all_rows(&db, "SELECT id FROM t WHERE g = 3 ORDER BY v LIMIT 2"),
+        [vec![RecordValue::Int(20)], vec![RecordValue::Int(2008)]]
+    );
+    assert!(
+        misses(&db) - before <= 9,
+        "{} pages read",
+        misses(&db) - before
+    );
+}
+
+#[test]
+fn values_spanning_overflow_pages_read_back_whole() {
+    let db = fixture("overflow.db");
+    let body: String = (0..600).map(|i| format!("{:04} ", i)).collect();
+    let data: Vec<u8> = (0..600)
+        .flat_map(|i| format!("{:03x};", i).into_bytes())
+        .collect();
+    let long_row = vec![
+        RecordValue::Int(2),
+        RecordValue::Text(body.clone()),
+        RecordValue::Blob(data),
+    ];
+
+    // Table scan.
+    let rows = all_rows(&db, "SELECT id, body, data FROM docs");
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[1], long_row);
+
+    // Through the index on title, then by rowid into the table.
+    let rows = all_rows(&db, "SELECT id, body, data FROM docs WHERE title = 'long'");
+    assert_eq!(rows, vec![long_row.clone()]);
+
+    // Through an index whose own entries overflow.
+    let sql = format!("SELECT id, body, data FROM docs WHERE body = '{}'", body);
+    assert_eq!(all_rows(&db, &sql), [long_row]);
+    let index = db.schema().unwrap().indexes_on("docs").last().unwrap();
+    assert_eq!(index.name, "docs_body");
+    let rowids = db
+        .lookup_rowids(index.root_page, &[RecordValue::Text(body)])
+        .unwrap();
+    assert_eq!(rowids, [2]);
+}
+
+fn text(value: &str) -> RecordValue {
+    RecordValue::Text(value.to_string())
+}
+
+#[test]
+fn inner_join_on_the_integer_primary_key() {
+    let db = fixture("joins.db");
+    let rows = all_rows(
+        &db,
+        "SELECT o.id, c.name FROM orders o JOIN customers c ON c.id = o.customer_id ORDER BY o.id",
+    );
+    // Order 13's customer doesn't exist, so it drops out.
+    assert_eq!(
+        rows,
+        [
+            vec![RecordValue::Int(10), text("Ann")],
+            vec![RecordValue::Int(11), text("Bob")],
+            vec![RecordValue::Int(12), text("Ann")],
+        ]
+    );
+}
+
+#[test]
+fn left_join_keeps_rows_without_a_match() {
+    let db = fixture("joins.db");
+    // Through the index on orders.customer_id: Cy has no orders.
+    let rows = all_rows(
+        &db,
+        "SELECT c.name, o.item FROM customers c LEFT JOIN orders o ON o.customer_id = c.id \
+         ORDER BY c.id, o.id",
+    );
+    assert_eq!(
+        rows,
+        [
+            vec![text("Ann"), text("apple")],
+            vec![text("Ann"), text("plum")],
+            vec![text("Bob"), text("pear")],
+            vec![text("Cy"), RecordValue::Null],
+        ]
+    );
+
+    // Through the rowid: order 13 points at a customer that isn't there.
+    let rows = all_rows(
+        &db,
+        "SELECT o.item, c.name FROM orders o LEFT JOIN customers c ON c.id = o.customer_id \
+         WHERE o.id > 11",
+    );
+    assert_eq!(
+        rows,
+        [
+            vec![text("plum"), text("Ann")],
+            vec![text("fig"), RecordValue::Null],
+        ]
+    );
+
+    // WHERE runs after the join, so it can pick out the unmatched rows.
+    let rows = all_rows(
+        &db,
+        "SELECT c.name FROM customers c LEFT JOIN orders o ON o.customer_id = c.id \
+         WHERE o.id IS NULL",
+    );
+    assert_eq!(rows, [vec![text("Cy")]]);
+}
+
+#[test]
+fn comma_join_uses_where_terms_to_reach_the_second_table() {
+    let db = fixture("joins.db");
+    let rows = all_rows(
+        &db,
+        "SELECT c.name, o.item FROM customers c, orders o \
+         WHERE o.customer_id = c.id AND c.name = 'Ann' ORDER BY o.item DESC",
+    );
+    assert_eq!(
+        rows,
+        [
+            vec![text("Ann"), text("plum")],
+            vec![text("Ann"), text("apple")],
+        ]
+    );
+}
+
+#[test]
+fn utf16le_text_compares_in_its_stored_byte_order() {
+    let db = fixture("utf16le.db");
+    let expected: Vec<Vec<RecordValue>> = ["Ā", "😀", "b", "héllo", "zz"]
+        .iter()
+        .map(|s| vec![text(s)])
+        .collect();
+    // Read in u_s order, then sorted after a rowid range scan.
+    assert_eq!(all_rows(&db, "SELECT s FROM u ORDER BY s"), expected);
+    assert_eq!(
+        all_rows(&db, "SELECT s FROM u WHERE id > 0 ORDER BY s"),
+        expected
+    );
+    assert_eq!(all_rows(&db, "SELECT s FROM u GROUP BY s"), expected);
+    assert_eq!(
+        all_rows(&db, "SELECT min(s), max(s) FROM u"),
+        [vec![text("Ā"), text("zz")]]
+    );
+    assert_eq!(
+        all_rows(&db, "SELECT s FROM u WHERE s > 'b' ORDER BY id"),
+        [vec![text("héllo")], vec![text("zz")]]
+    );
+}